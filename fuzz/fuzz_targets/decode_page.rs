@@ -0,0 +1,46 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sqlite_starter_rust_fuzz::header_handler::DatabaseHeader;
+use sqlite_starter_rust_fuzz::tree_handler::{BTreeCell, BTreePage};
+
+const PAGE_SIZE: usize = 512;
+
+/// Database header for 512 byte pages, with the text encoding taken from the
+/// input.
+fn header(text_encoding: u8) -> DatabaseHeader {
+    let mut header = [0; DatabaseHeader::SIZE];
+    header[..16].copy_from_slice(b"SQLite format 3\0");
+    header[16..18].copy_from_slice(&(PAGE_SIZE as u16).to_be_bytes());
+    header[21..24].copy_from_slice(&[64, 32, 32]);
+    header[59] = text_encoding % 4;
+
+    DatabaseHeader::new(&header).unwrap()
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Some((text_encoding, data)) = data.split_first() else {
+        return;
+    };
+
+    let mut page = data.to_vec();
+    page.resize(PAGE_SIZE, 0);
+
+    let Ok(page) = BTreePage::new(2, page.into(), &header(*text_encoding)) else {
+        return;
+    };
+
+    for cell in page.cells() {
+        let Ok(cell) = cell else {
+            continue;
+        };
+
+        let _ = page.cell_size(&cell);
+        let _ = match &cell {
+            BTreeCell::InteriorTableCell(_) => continue,
+            BTreeCell::InteriorIndexCell(cell) => cell.values(),
+            BTreeCell::LeafTableCell(cell) => cell.values(),
+            BTreeCell::LeafIndexCell(cell) => cell.values(),
+        };
+    }
+});
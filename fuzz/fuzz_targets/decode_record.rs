@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sqlite_starter_rust_fuzz::header_handler::TextEncoding;
+use sqlite_starter_rust_fuzz::tree_handler::values;
+
+fuzz_target!(|data: &[u8]| {
+    for text_encoding in [
+        TextEncoding::Utf8,
+        TextEncoding::Utf16le,
+        TextEncoding::Utf16be,
+    ] {
+        let _ = values(data, text_encoding, 2, 0);
+    }
+});
@@ -0,0 +1,24 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sqlite_starter_rust_fuzz::schema_handler::{IndexDefinition, TableDefinition, ViewDefinition};
+
+fuzz_target!(|sql: &str| {
+    if let Ok(definition) = TableDefinition::new(sql) {
+        for column in &definition.columns {
+            let _ = column.default_value();
+        }
+
+        let _ = definition.primary_key();
+        let _ = definition.rowid_alias();
+
+        for number in 0..4 {
+            if let Ok(index) = definition.autoindex("sqlite_autoindex", number) {
+                let _ = index.seek_columns(&definition);
+            }
+        }
+    }
+
+    let _ = IndexDefinition::new(sql);
+    let _ = ViewDefinition::new(sql);
+});
@@ -0,0 +1,115 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sqlite_starter_rust_fuzz::database_handler::Database;
+use sqlite_starter_rust_fuzz::sql_handler::Statement;
+use sqlite_starter_rust_fuzz::vfs_handler::MemorySource;
+
+const PAGE_SIZE: usize = 512;
+const MAX_PAGES: usize = 8;
+const PAGE_TYPES: [u8; 4] = [0x02, 0x05, 0x0a, 0x0d];
+
+/// Schema of the database: a table rooted at page 2 and an index on it
+/// rooted at page 3, leaving the pages of both trees to the input.
+const SCHEMA: [(&str, &str, u8, &str); 2] = [
+    ("table", "t", 2, "CREATE TABLE t(a, b)"),
+    ("index", "i", 3, "CREATE INDEX i ON t(a)"),
+];
+
+const QUERIES: [&str; 4] = [
+    "SELECT count(*) FROM t",
+    "SELECT * FROM t WHERE b = 1",
+    "SELECT b FROM t WHERE a = 1",
+    "PRAGMA integrity_check",
+];
+
+/// A record of text values and one small integer, as sqlite_schema holds.
+fn schema_record(r#type: &str, name: &str, rootpage: u8, sql: &str) -> Vec<u8> {
+    let texts = [r#type, name, name, sql];
+    let mut header = vec![0];
+    let mut body = Vec::new();
+
+    for text in &texts[..3] {
+        header.push((13 + 2 * text.len()) as u8);
+        body.extend_from_slice(text.as_bytes());
+    }
+
+    header.push(1);
+    body.push(rootpage);
+
+    header.push((13 + 2 * sql.len()) as u8);
+    body.extend_from_slice(sql.as_bytes());
+
+    header[0] = header.len() as u8;
+    header.extend(body);
+    header
+}
+
+/// Page 1: the database header followed by a leaf table page holding the
+/// schema records.
+fn first_page(page_count: usize) -> Vec<u8> {
+    let mut page = vec![0; PAGE_SIZE];
+    page[..16].copy_from_slice(b"SQLite format 3\0");
+    page[16..18].copy_from_slice(&(PAGE_SIZE as u16).to_be_bytes());
+    page[18..24].copy_from_slice(&[1, 1, 0, 64, 32, 32]);
+    page[28..32].copy_from_slice(&(page_count as u32).to_be_bytes());
+    page[44..48].copy_from_slice(&4u32.to_be_bytes());
+    page[56..60].copy_from_slice(&1u32.to_be_bytes());
+
+    page[100] = 0x0d;
+    page[103..105].copy_from_slice(&(SCHEMA.len() as u16).to_be_bytes());
+
+    let mut content_start = PAGE_SIZE;
+
+    for (row_id, (r#type, name, rootpage, sql)) in SCHEMA.iter().enumerate() {
+        let record = schema_record(r#type, name, *rootpage, sql);
+        let mut cell = vec![record.len() as u8, row_id as u8 + 1];
+        cell.extend(record);
+
+        content_start -= cell.len();
+        page[content_start..content_start + cell.len()].copy_from_slice(&cell);
+
+        let pointer = 108 + row_id * 2;
+        page[pointer..pointer + 2].copy_from_slice(&(content_start as u16).to_be_bytes());
+    }
+
+    page[105..107].copy_from_slice(&(content_start as u16).to_be_bytes());
+    page
+}
+
+fuzz_target!(|data: &[u8]| {
+    // The rest of the pages come from the input, each starting with one of
+    // the four b-tree page types so that they parse far enough to be walked.
+    let pages = data.chunks(PAGE_SIZE).take(MAX_PAGES - 1);
+    let page_count = pages.len() + 1;
+    let mut image = first_page(page_count);
+
+    for chunk in pages {
+        let mut page = chunk.to_vec();
+        page.resize(PAGE_SIZE, 0);
+
+        if !PAGE_TYPES.contains(&page[0]) {
+            page[0] = PAGE_TYPES[page[0] as usize % PAGE_TYPES.len()];
+        }
+
+        image.extend(page);
+    }
+
+    let Ok(database) = Database::from_source(Box::new(MemorySource::from(image))) else {
+        return;
+    };
+
+    for query in QUERIES {
+        match Statement::new(query) {
+            Ok(Statement::Select(statement)) => {
+                if let Ok(records) = database.records(statement) {
+                    records.for_each(drop);
+                }
+            }
+            Ok(Statement::Pragma(pragma)) => {
+                let _ = database.pragma(&pragma);
+            }
+            Err(_) => (),
+        }
+    }
+});
@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sqlite_starter_rust_fuzz::tokenizer::Tokenizer;
+
+fuzz_target!(|sql: &str| {
+    let mut tokenizer = Tokenizer::new(sql);
+
+    while tokenizer.next().is_some() {
+        let _ = tokenizer.remaining();
+    }
+});
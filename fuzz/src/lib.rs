@@ -0,0 +1,32 @@
+//! The modules of the main crate behind its command line, compiled on their
+//! own so the fuzz targets can reach them.
+#![allow(dead_code)]
+
+#[path = "../../src/cache_handler.rs"]
+pub mod cache_handler;
+#[path = "../../src/database_handler.rs"]
+pub mod database_handler;
+#[path = "../../src/error_handler.rs"]
+pub mod error_handler;
+#[path = "../../src/freelist_handler.rs"]
+pub mod freelist_handler;
+#[path = "../../src/header_handler.rs"]
+pub mod header_handler;
+#[path = "../../src/integrity_handler.rs"]
+pub mod integrity_handler;
+#[path = "../../src/mmap_handler.rs"]
+pub mod mmap_handler;
+#[path = "../../src/record_handler.rs"]
+pub mod record_handler;
+#[path = "../../src/schema_handler.rs"]
+pub mod schema_handler;
+#[path = "../../src/sql_handler.rs"]
+pub mod sql_handler;
+#[path = "../../src/tokenizer.rs"]
+pub mod tokenizer;
+#[path = "../../src/tree_handler.rs"]
+pub mod tree_handler;
+#[path = "../../src/vfs_handler.rs"]
+pub mod vfs_handler;
+#[path = "../../src/wal_handler.rs"]
+pub mod wal_handler;
@@ -0,0 +1,109 @@
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+use super::tree_handler::BTreePage;
+
+/// Same as sqlite's default `cache_size` of -2000, i.e. 2000 KiB worth of pages.
+pub const DEFAULT_CACHE_SIZE: i64 = -2000;
+
+/// Least recently used cache of parsed b-tree pages.
+#[derive(Debug)]
+pub struct PageCache {
+    capacity: usize,
+    pages: HashMap<u32, (u64, Rc<BTreePage>)>,
+    recency: BTreeMap<u64, u32>,
+    tick: u64,
+    pub stats: PageCacheStats,
+}
+
+impl PageCache {
+    pub fn new(capacity: usize) -> Self {
+        PageCache {
+            capacity,
+            pages: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            stats: PageCacheStats::default(),
+        }
+    }
+
+    /// Converts a `cache_size` setting into a number of pages, where negative
+    /// values are a size in KiB as in sqlite.
+    pub fn capacity_for(cache_size: i64, page_size: u32) -> usize {
+        let pages = if cache_size < 0 {
+            cache_size.unsigned_abs().saturating_mul(1024) / page_size as u64
+        } else {
+            cache_size as u64
+        };
+
+        usize::try_from(pages).unwrap_or(usize::MAX)
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+
+        while self.pages.len() > self.capacity {
+            self.evict();
+        }
+    }
+
+    pub fn get(&mut self, page_number: u32) -> Option<Rc<BTreePage>> {
+        self.tick += 1;
+
+        match self.pages.get_mut(&page_number) {
+            Some((tick, page)) => {
+                self.recency.remove(tick);
+                self.recency.insert(self.tick, page_number);
+                *tick = self.tick;
+                self.stats.hits += 1;
+
+                Some(page.clone())
+            }
+            None => {
+                self.stats.misses += 1;
+
+                None
+            }
+        }
+    }
+
+    pub fn insert(&mut self, page_number: u32, page: Rc<BTreePage>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if let Some((tick, _)) = self.pages.remove(&page_number) {
+            self.recency.remove(&tick);
+        }
+
+        while self.pages.len() >= self.capacity {
+            self.evict();
+        }
+
+        self.tick += 1;
+        self.recency.insert(self.tick, page_number);
+        self.pages.insert(page_number, (self.tick, page));
+    }
+
+    fn evict(&mut self) {
+        if let Some((_, page_number)) = self.recency.pop_first() {
+            self.pages.remove(&page_number);
+            self.stats.evictions += 1;
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PageCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+impl std::fmt::Display for PageCacheStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{:<22} {}", "Page cache hits:", self.hits)?;
+        writeln!(f, "{:<22} {}", "Page cache misses:", self.misses)?;
+        write!(f, "{:<22} {}", "Page cache evictions:", self.evictions)
+    }
+}
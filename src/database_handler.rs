@@ -0,0 +1,1085 @@
+use super::cache_handler::PageCacheStats;
+use super::error_handler::{DecodeError, DecodeErrorKind};
+use super::header_handler::{DatabaseHeader, TextEncoding};
+use super::integrity_handler::{IntegrityChecker, DEFAULT_MAX_ERRORS};
+use super::record_handler::{RecordFormat, RecordValue, SchemaRecord};
+use super::schema_handler::TableDefinition;
+use super::sql_handler::{
+    MappedSqlColumnVariant, MappedSqlStatement, MappedSqlWhereClause, PragmaStatement, SqlStatement,
+};
+use super::tree_handler::{
+    BTreeCell, BTreeInteriorIndexCell, BTreeLeafTableCell, BTreePage, BTreePageType, PageReader,
+    RecordHeader,
+};
+use super::vfs_handler::{PageAccess, PageBytes, PageSource};
+
+use anyhow::{anyhow, bail, Result};
+use std::cell::{OnceCell, RefCell};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+/// An open database file together with its schema.
+pub struct Database {
+    page_reader: RefCell<PageReader>,
+    pub schema: Schema,
+}
+
+impl Database {
+    pub fn open(file_name: &str, access: PageAccess) -> Result<Self> {
+        Self::with_page_reader(PageReader::new(file_name, access)?)
+    }
+
+    /// Opens a database held by any byte source, such as an in-memory image.
+    pub fn from_source(source: Box<dyn PageSource>) -> Result<Self> {
+        Self::with_page_reader(PageReader::from_source(source)?)
+    }
+
+    fn with_page_reader(page_reader: PageReader) -> Result<Self> {
+        let mut database = Database {
+            page_reader: RefCell::new(page_reader),
+            schema: Schema::default(),
+        };
+
+        database.schema = Schema::new(&database)?;
+
+        Ok(database)
+    }
+
+    pub fn read_page(&self, page_num: u32) -> Result<Rc<BTreePage>> {
+        self.page_reader.borrow_mut().read_page(page_num)
+    }
+
+    /// Decodes a cell of a page together with the part of its payload that
+    /// spilled into overflow pages.
+    pub fn cell<'p>(&self, page: &'p BTreePage, index: usize) -> Option<Result<BTreeCell<'p>>> {
+        let cell = page.cell(index)?.map_err(anyhow::Error::from);
+
+        Some(cell.and_then(|mut cell| {
+            self.page_reader.borrow_mut().read_overflow(&mut cell)?;
+            Ok(cell)
+        }))
+    }
+
+    /// Reads a page without parsing it, for pages outside of any b-tree.
+    pub fn read_raw_page(&self, page_num: u32) -> Result<PageBytes> {
+        self.page_reader.borrow_mut().read_raw_page(page_num)
+    }
+
+    pub fn page_count(&self) -> u32 {
+        self.page_reader.borrow().page_count()
+    }
+
+    pub fn header(&self) -> DatabaseHeader {
+        *self.page_reader.borrow().header()
+    }
+
+    pub fn cache_stats(&self) -> PageCacheStats {
+        self.page_reader.borrow().cache_stats()
+    }
+
+    pub fn pragma(&self, pragma: &PragmaStatement) -> Result<Vec<Record>> {
+        match (pragma.name.as_str(), &pragma.value) {
+            ("cache_size", None) => Ok(vec![Record {
+                values: vec![RecordFormat::Integer64(
+                    self.page_reader.borrow().cache_size(),
+                )],
+            }]),
+            ("cache_size", Some(value)) => {
+                let cache_size = value
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid cache_size '{}'", value))?;
+                self.page_reader.borrow_mut().set_cache_size(cache_size);
+
+                Ok(Vec::new())
+            }
+            ("freelist_count", None) => Ok(vec![Record {
+                values: vec![RecordFormat::Integer64(self.header().freelist_count as i64)],
+            }]),
+            ("page_count", None) => Ok(vec![Record {
+                values: vec![RecordFormat::Integer64(self.page_count() as i64)],
+            }]),
+            ("integrity_check", value) => {
+                let max_errors = match value {
+                    Some(value) => value
+                        .parse()
+                        .map_err(|_| anyhow!("Invalid integrity_check limit '{}'", value))?,
+                    None => DEFAULT_MAX_ERRORS,
+                };
+
+                Ok(IntegrityChecker::new(self, max_errors)
+                    .check(&self.schema.schema_records)
+                    .into_iter()
+                    .map(|message| Record {
+                        values: vec![RecordFormat::String(message)],
+                    })
+                    .collect())
+            }
+            (name, _) => bail!("Unsupported pragma '{}'", name),
+        }
+    }
+
+    pub fn records(&self, sql_statement: SqlStatement) -> Result<Records<'_>> {
+        if let Some(view) = self.schema.view(&sql_statement.table_name) {
+            return self.view_records(view, sql_statement);
+        }
+
+        let table = self.schema.table(&sql_statement.table_name)?;
+
+        let definition = table.table_definition()?;
+
+        if definition.without_rowid {
+            return self.without_rowid_records(table, definition, sql_statement);
+        }
+
+        let layout = Rc::new(TableLayout::new(definition)?);
+        let column_names = definition.column_names();
+
+        if let Some(where_clause) = &sql_statement.where_clause {
+            let index = self
+                .schema
+                .indexes(&sql_statement.table_name)
+                .map(|r| {
+                    let mapped_sql_statement = r.index_definition(definition).and_then(|index| {
+                        // A partial index only serves queries whose rows
+                        // all satisfy its WHERE.
+                        let columns = match &index.where_clause {
+                            Some(predicate) if !where_clause.implies(predicate) => Vec::new(),
+                            _ => index.seek_columns(definition),
+                        };
+
+                        sql_statement.map(&column_names, &columns)
+                    });
+
+                    let mut keys = Vec::new();
+
+                    if let Ok(mapped_sql_statement) = &mapped_sql_statement {
+                        keys = mapped_sql_statement.where_clause.as_ref().unwrap().keys();
+                    }
+
+                    (r.rootpage, mapped_sql_statement, keys)
+                })
+                .max_by_key(|(_, _, keys)| keys.len());
+
+            if let Some(index) = index {
+                let (rootpage, mapped_sql_statement, keys) = index;
+
+                if !keys.is_empty() {
+                    let index_iter = IndexIterator::new(self, self.read_page(rootpage)?, keys)?;
+                    let table_rootpage = table.rootpage;
+
+                    let record_iter = index_iter.filter_map(move |values| {
+                        let row = values.and_then(|mut values| {
+                            let row_id = values
+                                .pop()
+                                .and_then(|row_id| row_id.as_i64())
+                                .ok_or_else(|| anyhow!("Index entry has no rowid"))?;
+
+                            self.row(table_rootpage, row_id)
+                        });
+
+                        row.transpose()
+                    });
+
+                    return Records::new(
+                        Box::new(
+                            record_iter.map(move |cell| Row::from_cell(cell?, layout.clone())),
+                        ),
+                        mapped_sql_statement?,
+                    );
+                }
+            }
+        }
+
+        let cell_iter = CellIterator::new(self, self.read_page(table.rootpage)?)?;
+        let mapped_sql_statement = sql_statement.map(&column_names, &[])?;
+        Records::new(
+            Box::new(cell_iter.map(move |cell| Row::from_cell(cell?, layout.clone()))),
+            mapped_sql_statement,
+        )
+    }
+
+    /// Rows of a view, which runs the view's query as a subquery and
+    /// filters and projects its results like the rows of a table.
+    fn view_records(
+        &self,
+        view: &SchemaRecord,
+        sql_statement: SqlStatement,
+    ) -> Result<Records<'_>> {
+        let query = self.schema.view_query(view)?;
+        let column_names = self.schema.column_names(&view.name)?;
+        let mapped_sql_statement = sql_statement.map(&column_names, &[])?;
+
+        let record_iter = self.records(query)?.map(|record| record.map(Row::Record));
+
+        Records::new(Box::new(record_iter), mapped_sql_statement)
+    }
+
+    /// Rows of a WITHOUT ROWID table, which live in an index b-tree keyed by
+    /// the primary key, so equalities on its leading columns become seeks.
+    fn without_rowid_records(
+        &self,
+        table: &SchemaRecord,
+        definition: &TableDefinition,
+        sql_statement: SqlStatement,
+    ) -> Result<Records<'_>> {
+        let layout = TableLayout::new(definition)?;
+        let key_columns = definition.seek_columns(&definition.primary_key_index());
+        let mapped_sql_statement = sql_statement.map(&definition.column_names(), &key_columns)?;
+
+        let keys = match &mapped_sql_statement.where_clause {
+            Some(where_clause) => where_clause.keys(),
+            None => Vec::new(),
+        };
+
+        let index_iter = IndexIterator::new(self, self.read_page(table.rootpage)?, keys)?;
+        let record_iter = index_iter.map(move |values| {
+            values.and_then(|values| Record::with_column_order(values, &layout).map(Row::Record))
+        });
+
+        Records::new(Box::new(record_iter), mapped_sql_statement)
+    }
+
+    /// Looks up a single row of a rowid table, binary searching each page
+    /// on the way down.
+    fn row(&self, rootpage: u32, row_id: i64) -> Result<Option<TableCell<'_>>> {
+        let mut page = self.read_page(rootpage)?;
+
+        // A corrupt tree could point back up, so the descent is bounded.
+        for _ in 0..self.page_count() {
+            let index = page.search_row_id(row_id)?;
+
+            let left_child_page = match page.cell(index).transpose()? {
+                Some(BTreeCell::LeafTableCell(cell)) if cell.row_id.value == row_id => {
+                    return Ok(Some(TableCell::new(self, page.clone(), index)));
+                }
+                Some(BTreeCell::InteriorTableCell(cell)) => cell.left_child_page,
+                None => match page.right_most_pointer {
+                    Some(right_most_pointer) => right_most_pointer,
+                    None => return Ok(None),
+                },
+                Some(_) => return Ok(None),
+            };
+
+            page = self.read_page(left_child_page)?;
+
+            if !matches!(
+                page.page_type,
+                BTreePageType::InteriorTablePage | BTreePageType::LeafTablePage
+            ) {
+                return Err(DecodeErrorKind::UnexpectedPageType("table b-tree")
+                    .at(page.page_number, 0)
+                    .into());
+            }
+        }
+
+        bail!("Table b-tree {} is deeper than the database", rootpage)
+    }
+}
+
+/// In-order iterator over the rows of a table b-tree.
+pub struct CellIterator<'a> {
+    database: &'a Database,
+    /// Pages from the root down to the one being read, each with the index
+    /// of its next cell.
+    stack: Vec<(Rc<BTreePage>, usize)>,
+    /// Pages descended into so far, which a sound b-tree never revisits.
+    visited: HashSet<u32>,
+    flag: bool,
+}
+
+impl<'a> CellIterator<'a> {
+    pub fn new(database: &'a Database, page: Rc<BTreePage>) -> Result<Self> {
+        check_table_page(&page)?;
+
+        Ok(CellIterator {
+            database,
+            visited: HashSet::from([page.page_number]),
+            stack: vec![(page, 0)],
+            flag: false,
+        })
+    }
+
+    /// Descends into a child of `parent`, refusing a page the scan has
+    /// already been through rather than looping over it.
+    fn push(&mut self, parent: u32, page_number: u32) -> Result<()> {
+        if !self.visited.insert(page_number) {
+            return Err(DecodeErrorKind::RepeatedPage(page_number)
+                .at(parent, 0)
+                .into());
+        }
+
+        let page = self.database.read_page(page_number)?;
+        check_table_page(&page)?;
+        self.stack.push((page, 0));
+
+        Ok(())
+    }
+
+    /// Ends the iteration with an error.
+    fn fail(&mut self, error: anyhow::Error) -> Option<Result<TableCell<'a>>> {
+        self.flag = true;
+        Some(Err(error))
+    }
+}
+
+fn check_table_page(page: &BTreePage) -> Result<()> {
+    if !matches!(
+        page.page_type,
+        BTreePageType::InteriorTablePage | BTreePageType::LeafTablePage
+    ) {
+        return Err(DecodeErrorKind::UnexpectedPageType("table b-tree")
+            .at(page.page_number, 0)
+            .into());
+    }
+
+    Ok(())
+}
+
+impl<'a> Iterator for CellIterator<'a> {
+    type Item = Result<TableCell<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.flag {
+                return None;
+            }
+
+            let (page, index) = self.stack.last_mut()?;
+            let page = page.clone();
+            let cell_index = *index;
+            *index += 1;
+
+            match page.cell(cell_index) {
+                Some(Ok(BTreeCell::LeafTableCell(_))) => {
+                    return Some(Ok(TableCell::new(self.database, page.clone(), cell_index)))
+                }
+                Some(Ok(BTreeCell::InteriorTableCell(cell))) => {
+                    if let Err(error) = self.push(page.page_number, cell.left_child_page) {
+                        return self.fail(error);
+                    }
+                }
+                Some(Ok(_)) => return self.fail(anyhow!("Index cell in a table b-tree")),
+                Some(Err(error)) => return self.fail(error.into()),
+                None => {
+                    let right_most_pointer = if cell_index == page.cell_count() {
+                        page.right_most_pointer
+                    } else {
+                        None
+                    };
+
+                    match right_most_pointer {
+                        Some(right_most_pointer) => {
+                            if let Err(error) = self.push(page.page_number, right_most_pointer) {
+                                return self.fail(error);
+                            }
+                        }
+                        None => {
+                            self.stack.pop();
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// In-order iterator over the entries of an index b-tree whose leading
+/// columns equal `keys`, or over every entry when there are no keys.
+struct IndexIterator<'a> {
+    database: &'a Database,
+    stack: Vec<IndexCursor>,
+    /// Pages descended into so far, which a sound b-tree never revisits.
+    visited: HashSet<u32>,
+    keys: Vec<String>,
+    /// Encoding text is stored in, whose bytes the BINARY collation orders.
+    text_encoding: TextEncoding,
+    flag: bool,
+}
+
+struct IndexCursor {
+    page: Rc<BTreePage>,
+    index: usize,
+    /// What the interior cell whose left child is being visited does once
+    /// the child is done.
+    pending: Option<Pending>,
+}
+
+/// The entry of an interior index cell, handled after its left child.
+enum Pending {
+    /// The entry matches the keys and comes next.
+    Emit(Vec<RecordFormat>),
+    /// The entry sorts after the keys, so the range ends with the child.
+    Stop,
+}
+
+impl IndexCursor {
+    fn new(page: Rc<BTreePage>) -> Result<Self> {
+        if !matches!(
+            page.page_type,
+            BTreePageType::InteriorIndexPage | BTreePageType::LeafIndexPage
+        ) {
+            return Err(DecodeErrorKind::UnexpectedPageType("index b-tree")
+                .at(page.page_number, 0)
+                .into());
+        }
+
+        Ok(IndexCursor {
+            page,
+            index: 0,
+            pending: None,
+        })
+    }
+}
+
+impl<'a> IndexIterator<'a> {
+    fn new(database: &'a Database, page: Rc<BTreePage>, keys: Vec<String>) -> Result<Self> {
+        let mut index_iter = IndexIterator {
+            database,
+            stack: Vec::new(),
+            visited: HashSet::from([page.page_number]),
+            keys,
+            text_encoding: database.header().text_encoding,
+            flag: false,
+        };
+
+        let mut cursor = IndexCursor::new(page)?;
+        cursor.index = index_iter.seek(&cursor.page)?;
+        index_iter.stack.push(cursor);
+
+        Ok(index_iter)
+    }
+
+    /// Descends into a child of `parent`, starting at its first entry that
+    /// doesn't sort before the keys. A page the iteration has already been
+    /// through is refused rather than looped over.
+    fn push(&mut self, parent: u32, page_number: u32) -> Result<()> {
+        if !self.visited.insert(page_number) {
+            return Err(DecodeErrorKind::RepeatedPage(page_number)
+                .at(parent, 0)
+                .into());
+        }
+
+        let mut cursor = IndexCursor::new(self.database.read_page(page_number)?)?;
+        cursor.index = self.seek(&cursor.page)?;
+        self.stack.push(cursor);
+
+        Ok(())
+    }
+
+    /// Binary searches a page for the first entry not less than the keys.
+    fn seek(&self, page: &BTreePage) -> Result<usize> {
+        let (mut low, mut high) = (0, page.cell_count());
+
+        if self.keys.is_empty() {
+            return Ok(low);
+        }
+
+        while low < high {
+            let middle = (low + high) / 2;
+
+            if self.compare(page, middle)? == Ordering::Less {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+
+        Ok(low)
+    }
+
+    /// Ends the iteration with an error.
+    fn fail(&mut self, error: anyhow::Error) -> Option<Result<Vec<RecordFormat>>> {
+        self.flag = true;
+        Some(Err(error))
+    }
+
+    /// Decodes the entry at `index` of `page` and its record header. The
+    /// overflow chain is only read when the header or one of the first
+    /// `columns` columns isn't on the page.
+    fn entry<'p>(
+        &self,
+        page: &'p BTreePage,
+        index: usize,
+        columns: usize,
+    ) -> Result<(BTreeCell<'p>, RecordHeader)> {
+        let cell = match page.cell(index).transpose()? {
+            Some(cell @ (BTreeCell::InteriorIndexCell(_) | BTreeCell::LeafIndexCell(_))) => cell,
+            _ => bail!("Table cell in an index b-tree"),
+        };
+
+        if let (Some(Ok(header)), Some(payload)) = (cell.record_header(), cell.payload()) {
+            let columns = columns.min(header.column_count());
+
+            if (0..columns).all(|column| header.is_within(column, payload.len())) {
+                return Ok((cell, header));
+            }
+        }
+
+        let cell = self
+            .database
+            .cell(page, index)
+            .transpose()?
+            .ok_or_else(|| anyhow!("Index entry {} is missing", index))?;
+        let header = cell
+            .record_header()
+            .ok_or_else(|| anyhow!("Table cell in an index b-tree"))??;
+
+        Ok((cell, header))
+    }
+
+    /// Orders an index entry against the keys, decoding only the columns
+    /// it has to and without copying them out of the page.
+    fn compare(&self, page: &BTreePage, index: usize) -> Result<Ordering> {
+        let (cell, header) = self.entry(page, index, self.keys.len())?;
+
+        for (index, key) in self.keys.iter().enumerate() {
+            let Some(value) = cell.value(&header, index).transpose()? else {
+                break;
+            };
+
+            match value.compare_key(key, self.text_encoding) {
+                Ordering::Equal => continue,
+                ordering => return Ok(ordering),
+            }
+        }
+
+        Ok(Ordering::Equal)
+    }
+
+    /// Decodes every column of an index entry, overflow included.
+    fn values(&self, page: &BTreePage, index: usize) -> Result<Vec<RecordFormat>> {
+        match self.database.cell(page, index).transpose()? {
+            Some(cell) => match cell.values() {
+                Some(values) => Ok(values?),
+                None => bail!("Table cell in an index b-tree"),
+            },
+            None => bail!("Index entry {} is missing", index),
+        }
+    }
+}
+
+impl Iterator for IndexIterator<'_> {
+    type Item = Result<Vec<RecordFormat>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.flag {
+                return None;
+            }
+
+            let cursor = self.stack.last_mut()?;
+            let page = cursor.page.clone();
+
+            // Back from the left child, the interior cell's own entry is next.
+            if let Some(pending) = cursor.pending.take() {
+                cursor.index += 1;
+
+                match pending {
+                    Pending::Emit(values) => return Some(Ok(values)),
+                    Pending::Stop => {
+                        self.flag = true;
+                        continue;
+                    }
+                }
+            }
+
+            let index = cursor.index;
+
+            match page.cell(index) {
+                Some(Err(error)) => return self.fail(error.into()),
+                Some(Ok(BTreeCell::LeafIndexCell(_))) => {
+                    cursor.index += 1;
+
+                    match self.compare(&page, index) {
+                        Ok(Ordering::Less) => continue,
+                        Ok(Ordering::Equal) => return Some(self.values(&page, index)),
+                        Ok(Ordering::Greater) => self.flag = true,
+                        Err(error) => return self.fail(error),
+                    }
+                }
+                Some(Ok(BTreeCell::InteriorIndexCell(BTreeInteriorIndexCell {
+                    left_child_page,
+                    ..
+                }))) => {
+                    let ordering = match self.compare(&page, index) {
+                        Ok(ordering) => ordering,
+                        Err(error) => return self.fail(error),
+                    };
+
+                    // Everything left of an entry sorts before it.
+                    if ordering == Ordering::Less {
+                        self.stack.last_mut()?.index += 1;
+                        continue;
+                    }
+
+                    let pending = match ordering {
+                        Ordering::Equal => match self.values(&page, index) {
+                            Ok(values) => Pending::Emit(values),
+                            Err(error) => return self.fail(error),
+                        },
+                        _ => Pending::Stop,
+                    };
+
+                    self.stack.last_mut()?.pending = Some(pending);
+
+                    if let Err(error) = self.push(page.page_number, left_child_page) {
+                        return self.fail(error);
+                    }
+                }
+                Some(_) => return self.fail(anyhow!("Table cell in an index b-tree")),
+                None => {
+                    let right_most_pointer = if cursor.index == page.cell_count() {
+                        page.right_most_pointer
+                    } else {
+                        None
+                    };
+
+                    cursor.index += 1;
+
+                    match right_most_pointer {
+                        Some(right_most_pointer) => {
+                            if let Err(error) = self.push(page.page_number, right_most_pointer) {
+                                return self.fail(error);
+                            }
+                        }
+                        None => {
+                            self.stack.pop();
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+struct FilteredRecords<'a> {
+    record_iter: Box<dyn Iterator<Item = Result<Row<'a>>> + 'a>,
+    mapped_sql_where_clause: MappedSqlWhereClause,
+}
+
+impl<'a> FilteredRecords<'a> {
+    fn new(
+        record_iter: Box<dyn Iterator<Item = Result<Row<'a>>> + 'a>,
+        mapped_sql_where_clause: MappedSqlWhereClause,
+    ) -> Self {
+        FilteredRecords {
+            record_iter,
+            mapped_sql_where_clause,
+        }
+    }
+}
+
+impl<'a> Iterator for FilteredRecords<'a> {
+    type Item = Result<Row<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let row = match self.record_iter.next()? {
+                Ok(row) => row,
+                Err(error) => return Some(Err(error)),
+            };
+
+            match self.mapped_sql_where_clause.matches(&row) {
+                Ok(true) => return Some(Ok(row)),
+                Ok(false) => continue,
+                Err(error) => return Some(Err(error)),
+            }
+        }
+    }
+}
+
+pub struct Records<'a> {
+    record_iter: Box<dyn Iterator<Item = Result<Row<'a>>> + 'a>,
+    column_variants: Vec<MappedSqlColumnVariant>,
+}
+
+impl<'a> Records<'a> {
+    fn new(
+        record_iter: Box<dyn Iterator<Item = Result<Row<'a>>> + 'a>,
+        sql_statement: MappedSqlStatement,
+    ) -> Result<Self> {
+        let mut record_iter = match sql_statement.where_clause {
+            Some(where_clause) => Box::new(FilteredRecords::new(record_iter, where_clause)),
+            None => record_iter,
+        };
+
+        if sql_statement.column_variants.first() == Some(&MappedSqlColumnVariant::Count) {
+            let count = record_iter.try_fold(0, |count, record| record.map(|_| count + 1))?;
+            let record_count = Record {
+                values: vec![RecordFormat::Integer64(count)],
+            };
+            record_iter = Box::new(std::iter::once(Ok(Row::Record(record_count))));
+        }
+
+        Ok(Records {
+            record_iter,
+            column_variants: sql_statement.column_variants,
+        })
+    }
+}
+
+impl Records<'_> {
+    /// Decodes the selected columns of a row, and only those.
+    fn project(&self, row: &Row) -> Result<Record> {
+        let mut values = Vec::new();
+
+        for c in &self.column_variants {
+            match c {
+                MappedSqlColumnVariant::Column(index) => values.push(row.column(*index)?),
+                MappedSqlColumnVariant::EveryColumn => {
+                    for index in 0..row.column_count() {
+                        values.push(row.column(index)?);
+                    }
+                }
+                MappedSqlColumnVariant::Count => values.push(row.column(0)?),
+            }
+        }
+
+        Ok(Record { values })
+    }
+}
+
+impl Iterator for Records<'_> {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let row = self.record_iter.next()?;
+
+        Some(row.and_then(|row| self.project(&row)))
+    }
+}
+
+/// How the records of a table map onto its columns.
+#[derive(Debug)]
+pub struct TableLayout {
+    /// Column that reads as the rowid rather than its stored NULL.
+    rowid_alias: Option<usize>,
+    /// Table column stored at each position of a record.
+    column_order: Vec<usize>,
+    /// Record position of each table column, the inverse of `column_order`.
+    record_positions: Vec<Option<usize>>,
+    /// What columns missing from records written before an `ALTER TABLE
+    /// ADD COLUMN` read as.
+    column_defaults: Vec<Option<RecordFormat>>,
+}
+
+impl TableLayout {
+    fn new(table: &TableDefinition) -> Result<Self> {
+        let column_order = table.record_column_order()?;
+        let column_defaults = table.column_defaults();
+        let mut record_positions = vec![None; column_defaults.len()];
+
+        for (position, column) in column_order.iter().enumerate() {
+            record_positions[*column] = Some(position);
+        }
+
+        Ok(TableLayout {
+            rowid_alias: table.rowid_alias()?,
+            column_order,
+            record_positions,
+            column_defaults,
+        })
+    }
+
+    /// The value of a column a record doesn't hold.
+    fn default(&self, index: usize) -> Option<Result<&RecordFormat>> {
+        let default = self.column_defaults.get(index)?;
+
+        if self.record_positions[index].is_none() {
+            return Some(Err(anyhow!(
+                "Virtual generated column {} is not supported",
+                index
+            )));
+        }
+
+        Some(default.as_ref().ok_or_else(|| {
+            anyhow!(
+                "Unsupported DEFAULT of column {} in a record predating it",
+                index
+            )
+        }))
+    }
+}
+
+/// A leaf cell of a table b-tree, read from its page whenever it is
+/// needed. A payload spilling into overflow pages is only put together
+/// from the chain, and copied, once a column past the page is read.
+pub struct TableCell<'a> {
+    database: &'a Database,
+    page: Rc<BTreePage>,
+    index: usize,
+    /// The cell with its overflow read, once a column has needed it.
+    whole: OnceCell<BTreeLeafTableCell<'static>>,
+}
+
+impl<'a> TableCell<'a> {
+    fn new(database: &'a Database, page: Rc<BTreePage>, index: usize) -> Self {
+        TableCell {
+            database,
+            page,
+            index,
+            whole: OnceCell::new(),
+        }
+    }
+
+    /// Decodes the cell as it is stored on its page.
+    fn cell(&self) -> Result<BTreeLeafTableCell<'_>, DecodeError> {
+        match self.page.cell(self.index) {
+            Some(Ok(BTreeCell::LeafTableCell(cell))) => Ok(cell),
+            Some(Err(error)) => Err(error),
+            _ => {
+                Err(DecodeErrorKind::UnexpectedPageType("table b-tree")
+                    .at(self.page.page_number, 0))
+            }
+        }
+    }
+
+    /// The cell with its whole payload, reading the overflow chain the
+    /// first time.
+    fn whole(&self) -> Result<&BTreeLeafTableCell<'static>> {
+        if let Some(cell) = self.whole.get() {
+            return Ok(cell);
+        }
+
+        let cell = match self.database.cell(&self.page, self.index).transpose()? {
+            Some(BTreeCell::LeafTableCell(cell)) => cell.into_owned(),
+            _ => bail!("Index cell in a table b-tree"),
+        };
+
+        Ok(self.whole.get_or_init(|| cell))
+    }
+
+    /// Row id and the part of the payload stored on the page.
+    fn record(&self) -> Result<(i64, &[u8]), DecodeError> {
+        self.page.leaf_table_record(self.index)
+    }
+}
+
+/// A row on its way through filtering and projection. Rows of a table
+/// b-tree keep their cell and decode a column only when it is read.
+pub enum Row<'a> {
+    Cell(TableCell<'a>, RecordHeader, Rc<TableLayout>),
+    Record(Record),
+}
+
+impl<'a> Row<'a> {
+    fn from_cell(cell: TableCell<'a>, layout: Rc<TableLayout>) -> Result<Self> {
+        let header = {
+            let local = cell.cell()?;
+
+            match local.record_header() {
+                Ok(header) => header,
+                // A header too long for the page is read with the overflow.
+                Err(_) if local.first_overflow_page().is_some() => cell.whole()?.record_header()?,
+                Err(error) => return Err(error.into()),
+            }
+        };
+
+        Ok(Row::Cell(cell, header, layout))
+    }
+
+    fn column_count(&self) -> usize {
+        match self {
+            Row::Cell(_, _, layout) => layout.column_defaults.len(),
+            Row::Record(record) => record.values.len(),
+        }
+    }
+
+    /// Reads a column, borrowing text and blobs from the row.
+    pub fn value(&self, index: usize) -> Result<RecordValue<'_>> {
+        let value = match self {
+            Row::Cell(cell, header, layout) => {
+                let (row_id, payload) = cell.record()?;
+
+                if layout.rowid_alias == Some(index) {
+                    Some(RecordValue::Scalar(RecordFormat::Integer64(row_id)))
+                } else {
+                    match layout.record_positions.get(index) {
+                        Some(Some(position)) if *position < header.column_count() => {
+                            let payload = if header.is_within(*position, payload.len()) {
+                                payload
+                            } else {
+                                cell.whole()?.payload()
+                            };
+
+                            header.value(payload, *position).transpose()?
+                        }
+                        _ => layout.default(index).transpose()?.map(RecordValue::from),
+                    }
+                }
+            }
+            Row::Record(record) => record.values.get(index).map(RecordValue::from),
+        };
+
+        value.ok_or_else(|| anyhow!("Record has no column {}", index))
+    }
+
+    pub fn column(&self, index: usize) -> Result<RecordFormat> {
+        self.value(index).map(RecordValue::into_owned)
+    }
+}
+
+#[derive(Debug)]
+pub struct Record {
+    values: Vec<RecordFormat>,
+}
+
+impl Record {
+    /// Rearranges a record stored in the layout's column order into table
+    /// column order, filling in the columns it doesn't hold.
+    fn with_column_order(record_values: Vec<RecordFormat>, layout: &TableLayout) -> Result<Self> {
+        let mut values = vec![None; layout.column_defaults.len()];
+
+        for (value, column) in record_values.into_iter().zip(&layout.column_order) {
+            values[*column] = Some(value);
+        }
+
+        let values = values
+            .into_iter()
+            .enumerate()
+            .map(|(index, value)| match value {
+                Some(value) => Ok(value),
+                None => match layout.default(index) {
+                    Some(default) => default.cloned(),
+                    None => Ok(RecordFormat::NULL),
+                },
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Record { values })
+    }
+}
+
+impl std::fmt::Display for Record {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut values = self.values.iter();
+        if let Some(value) = values.next() {
+            write!(f, "{}", String::from(value))?;
+        }
+
+        for value in values {
+            write!(f, "|{}", String::from(value))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Names the table of schema records in page 1 goes by.
+const SCHEMA_TABLE_NAMES: [&str; 3] = ["sqlite_schema", "sqlite_master", "sqlite_temp_master"];
+
+#[derive(Default)]
+pub struct Schema {
+    schema_records: Vec<SchemaRecord>,
+}
+
+impl Schema {
+    fn new(database: &Database) -> Result<Self> {
+        Ok(Schema {
+            schema_records: CellIterator::new(database, database.read_page(1)?)?
+                .map(|cell| Ok(SchemaRecord::try_from(cell?.whole()?)?))
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    fn table(&self, table_name: &str) -> Result<&SchemaRecord> {
+        if SCHEMA_TABLE_NAMES.contains(&table_name) {
+            return Ok(SchemaRecord::schema_table());
+        }
+
+        self.schema_records
+            .iter()
+            .find(|r| r.tbl_name == table_name && r.r#type == "table")
+            .ok_or_else(|| anyhow!("Table '{}' not found", table_name))
+    }
+
+    /// Root page of the table or index b-tree with the given name.
+    pub fn rootpage(&self, name: &str) -> Result<u32> {
+        if SCHEMA_TABLE_NAMES.contains(&name) {
+            return Ok(1);
+        }
+
+        self.schema_records
+            .iter()
+            .find(|r| r.name == name && r.rootpage > 0)
+            .map(|r| r.rootpage)
+            .ok_or_else(|| anyhow!("No table or index named '{}'", name))
+    }
+
+    fn view(&self, name: &str) -> Option<&SchemaRecord> {
+        self.schema_records
+            .iter()
+            .find(|r| r.name == name && r.r#type == "view")
+    }
+
+    /// The query of a view, refusing a view that reads from itself through
+    /// the views it's built on.
+    fn view_query(&self, view: &SchemaRecord) -> Result<SqlStatement> {
+        let query = SqlStatement::new(&view.view_definition()?.select)?;
+        let mut expanded = vec![view.name.as_str()];
+        let mut source = query.table_name.clone();
+
+        while let Some(view) = self.view(&source) {
+            if expanded.contains(&view.name.as_str()) {
+                bail!("View {} is circularly defined", view.name);
+            }
+
+            expanded.push(&view.name);
+            source = SqlStatement::new(&view.view_definition()?.select)?.table_name;
+        }
+
+        Ok(query)
+    }
+
+    /// Column names of a table, or of a view: those listed after its name,
+    /// or else those its query selects.
+    fn column_names(&self, name: &str) -> Result<Vec<String>> {
+        let Some(view) = self.view(name) else {
+            return Ok(self.table(name)?.table_definition()?.column_names());
+        };
+
+        let query = self.view_query(view)?;
+        let selected = query.column_names(&self.column_names(&query.table_name)?);
+        let columns = view.view_definition()?.columns;
+
+        match columns.len() {
+            0 => Ok(selected),
+            n if n == selected.len() => Ok(columns),
+            n => bail!(
+                "Expected {} columns for '{}' but got {}",
+                n,
+                view.name,
+                selected.len()
+            ),
+        }
+    }
+
+    fn indexes<'a>(&'a self, table_name: &'a str) -> impl Iterator<Item = &'a SchemaRecord> {
+        self.schema_records
+            .iter()
+            .filter(move |r| r.tbl_name == table_name && r.r#type == "index")
+    }
+
+    /// Tables and views, as `.tables` lists them.
+    pub fn tables(&self) -> impl Iterator<Item = &SchemaRecord> {
+        self.schema_records
+            .iter()
+            .filter(|r| r.r#type == "table" || r.r#type == "view")
+    }
+
+    pub fn count(&self, r#type: &str) -> usize {
+        self.schema_records
+            .iter()
+            .filter(|r| r.r#type == r#type)
+            .count()
+    }
+
+    /// Total length of the schema SQL, as reported by `.dbinfo`.
+    pub fn size(&self) -> usize {
+        self.schema_records
+            .iter()
+            .map(|r| r.sql.as_ref().map_or(0, |sql| sql.chars().count()))
+            .sum()
+    }
+}
@@ -0,0 +1,49 @@
+use thiserror::Error;
+
+/// Malformed data found while decoding a page or a record.
+#[derive(Debug, Error)]
+#[error("Malformed page {page_number} at offset {offset}: {kind}")]
+pub struct DecodeError {
+    pub page_number: u32,
+    pub offset: usize,
+    pub kind: DecodeErrorKind,
+}
+
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum DecodeErrorKind {
+    #[error("unexpected end of data")]
+    UnexpectedEnd,
+    #[error("invalid page type {0}")]
+    InvalidPageType(u8),
+    #[error("expected a {0} page")]
+    UnexpectedPageType(&'static str),
+    #[error("{0} cell pointers do not fit on the page")]
+    TooManyCells(u16),
+    #[error("cell offset {0} is out of range")]
+    CellOffsetOutOfRange(usize),
+    #[error("invalid freeblock list")]
+    InvalidFreeblock,
+    #[error("invalid record header size {0}")]
+    InvalidRecordHeader(i64),
+    #[error("invalid serial type {0}")]
+    InvalidSerialType(i64),
+    #[error("overflow chain ended before the end of the payload")]
+    OverflowChainTooShort,
+    #[error("overflow chain is longer than the database")]
+    OverflowChainTooLong,
+    #[error("child page {0} is already part of the b-tree")]
+    RepeatedPage(u32),
+    #[error("invalid sqlite_schema record")]
+    InvalidSchemaRecord,
+}
+
+impl DecodeErrorKind {
+    /// Locates the error at a byte offset of a page.
+    pub fn at(self, page_number: u32, offset: usize) -> DecodeError {
+        DecodeError {
+            page_number,
+            offset,
+            kind: self,
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use super::database_handler::Database;
+
+use anyhow::{anyhow, bail, Result};
+
+/// A page on the freelist.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FreePage {
+    Trunk(u32),
+    Leaf(u32),
+}
+
+impl FreePage {
+    pub fn page_number(&self) -> u32 {
+        match self {
+            FreePage::Trunk(page_number) | FreePage::Leaf(page_number) => *page_number,
+        }
+    }
+}
+
+/// Iterator over the freelist, yielding each trunk page followed by the leaf
+/// pages it lists.
+pub struct FreelistIterator<'a> {
+    database: &'a Database,
+    next_trunk: u32,
+    leaves: std::vec::IntoIter<u32>,
+    remaining: u32,
+}
+
+impl<'a> FreelistIterator<'a> {
+    pub fn new(database: &'a Database) -> Self {
+        FreelistIterator {
+            database,
+            next_trunk: database.header().freelist_trunk_page,
+            leaves: Vec::new().into_iter(),
+            remaining: database.page_count(),
+        }
+    }
+
+    fn read_trunk(&mut self, page_number: u32) -> Result<FreePage> {
+        let page = self.database.read_raw_page(page_number)?;
+        let max_leaves = self.database.header().usable_size() as usize / 4 - 2;
+
+        let next_trunk = u32::from_be_bytes(page[..4].try_into()?);
+        let leaf_count = u32::from_be_bytes(page[4..8].try_into()?) as usize;
+
+        if leaf_count > max_leaves {
+            bail!("freelist leaf count too big on page {}", page_number);
+        }
+
+        self.next_trunk = next_trunk;
+        self.leaves = page[8..8 + leaf_count * 4]
+            .chunks(4)
+            .map(|chunk| u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect::<Vec<_>>()
+            .into_iter();
+
+        Ok(FreePage::Trunk(page_number))
+    }
+}
+
+impl Iterator for FreelistIterator<'_> {
+    type Item = Result<FreePage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let free_page = match self.leaves.next() {
+            Some(page_number) => Ok(FreePage::Leaf(page_number)),
+            None if self.next_trunk != 0 => {
+                let page_number = std::mem::take(&mut self.next_trunk);
+                self.read_trunk(page_number)
+            }
+            None => return None,
+        };
+
+        // A freelist longer than the database must loop back on itself.
+        if self.remaining == 0 {
+            self.next_trunk = 0;
+            self.leaves = Vec::new().into_iter();
+            return Some(Err(anyhow!("Freelist is longer than the database")));
+        }
+
+        self.remaining -= 1;
+        Some(free_page)
+    }
+}
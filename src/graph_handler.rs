@@ -0,0 +1,213 @@
+use super::database_handler::Database;
+use super::tree_handler::{BTreeCell, BTreePageType};
+
+use anyhow::Result;
+use std::collections::HashSet;
+use std::fmt;
+
+/// Longest key shown in a node before it is cut short.
+const MAX_KEY_LEN: usize = 24;
+
+/// Graphviz DOT graph of a b-tree, as printed by the `.graph NAME` command.
+pub struct BTreeGraph {
+    name: String,
+    lines: Vec<String>,
+}
+
+/// What is left to add to a graph, in the order its lines are written.
+enum Step {
+    Line(String),
+    Page(u32),
+    OverflowChain(u32),
+}
+
+impl BTreeGraph {
+    pub fn new(database: &Database, name: &str, rootpage: u32) -> Result<Self> {
+        let mut graph = BTreeGraph {
+            name: name.to_string(),
+            lines: Vec::new(),
+        };
+
+        // Pages are expanded from a stack rather than by recursion, so that
+        // a corrupt tree of any depth can't exhaust the call stack.
+        let mut steps = vec![Step::Page(rootpage)];
+        let mut visited = HashSet::new();
+
+        while let Some(step) = steps.pop() {
+            match step {
+                Step::Line(line) => graph.lines.push(line),
+                Step::Page(page_number) => {
+                    if visited.insert(page_number) {
+                        let page_steps = graph.add_page(database, page_number)?;
+                        steps.extend(page_steps.into_iter().rev());
+                    }
+                }
+                Step::OverflowChain(first_overflow_page) => {
+                    graph.add_overflow_chain(database, first_overflow_page, &mut visited)?
+                }
+            }
+        }
+
+        Ok(graph)
+    }
+
+    /// Adds the node of a page, returning the edges to its children and the
+    /// children themselves, left to right.
+    fn add_page(&mut self, database: &Database, page_number: u32) -> Result<Vec<Step>> {
+        let page = database.read_page(page_number)?;
+        let keys = (0..page.cell_count())
+            .map(|i| key(database.cell(&page, i)))
+            .collect::<Vec<_>>();
+
+        let page_type = match page.page_type {
+            BTreePageType::InteriorIndexPage => "interior index",
+            BTreePageType::InteriorTablePage => "interior table",
+            BTreePageType::LeafIndexPage => "leaf index",
+            BTreePageType::LeafTablePage => "leaf table",
+        };
+
+        let mut label = format!(
+            "page {}\\n{}\\n{} cells",
+            page_number,
+            page_type,
+            keys.len()
+        );
+        if let (Some(first), Some(last)) = (keys.first(), keys.last()) {
+            label.push_str(&format!("\\n{} .. {}", first, last));
+        }
+
+        let shape = match page.right_most_pointer {
+            Some(_) => "box",
+            None => "box, style=rounded",
+        };
+        self.lines.push(format!(
+            "page{} [label=\"{}\", shape={}];",
+            page_number, label, shape
+        ));
+
+        let mut steps = Vec::new();
+
+        for (i, cell) in page.cells().enumerate() {
+            let Ok(cell) = cell else {
+                continue;
+            };
+
+            let left_child_page = match &cell {
+                BTreeCell::InteriorIndexCell(cell) => Some(cell.left_child_page),
+                BTreeCell::InteriorTableCell(cell) => Some(cell.left_child_page),
+                _ => None,
+            };
+
+            if let Some(left_child_page) = left_child_page {
+                let bound = match page.page_type {
+                    BTreePageType::InteriorTablePage => "<=",
+                    _ => "<",
+                };
+
+                steps.push(Step::Line(format!(
+                    "page{} -> page{} [label=\"{} {}\"];",
+                    page_number, left_child_page, bound, keys[i]
+                )));
+                steps.push(Step::Page(left_child_page));
+            }
+
+            if let Some(first_overflow_page) = cell.first_overflow_page() {
+                steps.push(Step::Line(format!(
+                    "page{} -> page{} [label=\"cell {}\", style=dashed];",
+                    page_number, first_overflow_page, i
+                )));
+                steps.push(Step::OverflowChain(first_overflow_page));
+            }
+        }
+
+        if let Some(right_most_pointer) = page.right_most_pointer {
+            let label = keys
+                .last()
+                .map(|key| format!("> {}", key))
+                .unwrap_or_default();
+
+            steps.push(Step::Line(format!(
+                "page{} -> page{} [label=\"{}\"];",
+                page_number, right_most_pointer, label
+            )));
+            steps.push(Step::Page(right_most_pointer));
+        }
+
+        Ok(steps)
+    }
+
+    fn add_overflow_chain(
+        &mut self,
+        database: &Database,
+        first_overflow_page: u32,
+        visited: &mut HashSet<u32>,
+    ) -> Result<()> {
+        let mut page_number = first_overflow_page;
+
+        while page_number != 0 && visited.insert(page_number) {
+            let page = database.read_raw_page(page_number)?;
+            let next_page = u32::from_be_bytes(page[..4].try_into()?);
+
+            self.lines.push(format!(
+                "page{} [label=\"overflow {}\", shape=note];",
+                page_number, page_number
+            ));
+
+            if next_page != 0 {
+                self.lines.push(format!(
+                    "page{} -> page{} [style=dashed];",
+                    page_number, next_page
+                ));
+            }
+
+            page_number = next_page;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for BTreeGraph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "digraph \"{}\" {{", escape(&self.name))?;
+        writeln!(f, "  node [fontname=monospace];")?;
+
+        for line in &self.lines {
+            writeln!(f, "  {}", line)?;
+        }
+
+        writeln!(f, "}}")
+    }
+}
+
+/// The row id or index key of a cell, escaped for a DOT label.
+fn key(cell: Option<Result<BTreeCell<'_>>>) -> String {
+    let values = match cell {
+        Some(Ok(BTreeCell::InteriorTableCell(cell))) => return cell.row_id.value.to_string(),
+        Some(Ok(BTreeCell::LeafTableCell(cell))) => return cell.row_id.value.to_string(),
+        Some(Ok(BTreeCell::InteriorIndexCell(cell))) => cell.values().map_err(Into::into),
+        Some(Ok(BTreeCell::LeafIndexCell(cell))) => cell.values().map_err(Into::into),
+        Some(Err(error)) => Err(error),
+        None => Ok(Vec::new()),
+    };
+
+    let key = match values {
+        Ok(values) => values
+            .iter()
+            .map(String::from)
+            .collect::<Vec<_>>()
+            .join(", "),
+        Err(_) => String::from("?"),
+    };
+
+    let key = match key.char_indices().nth(MAX_KEY_LEN) {
+        Some((end, _)) => format!("{}...", &key[..end]),
+        None => key,
+    };
+
+    escape(&format!("({})", key))
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
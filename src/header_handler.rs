@@ -0,0 +1,131 @@
+use anyhow::{bail, Result};
+
+const HEADER_MAGIC: &[u8; 16] = b"SQLite format 3\0";
+
+/// The 100-byte header at the start of every database file.
+#[derive(Debug, Clone, Copy)]
+pub struct DatabaseHeader {
+    pub page_size: u32,
+    pub write_version: u8,
+    pub read_version: u8,
+    pub reserved_bytes: u8,
+    pub file_change_counter: u32,
+    pub page_count: u32,
+    pub freelist_trunk_page: u32,
+    pub freelist_count: u32,
+    pub schema_cookie: u32,
+    pub schema_format: u32,
+    pub default_cache_size: u32,
+    pub autovacuum_top_root: u32,
+    pub text_encoding: TextEncoding,
+    pub user_version: u32,
+    pub incremental_vacuum: u32,
+    pub application_id: u32,
+    pub version_valid_for: u32,
+    pub sqlite_version_number: u32,
+}
+
+impl DatabaseHeader {
+    pub const SIZE: usize = 100;
+
+    pub fn new(header: &[u8]) -> Result<Self> {
+        if header.len() < Self::SIZE {
+            bail!("Database header is truncated");
+        }
+
+        if &header[..16] != HEADER_MAGIC {
+            bail!("File is not a database");
+        }
+
+        let read_u32 = |offset: usize| {
+            u32::from_be_bytes([
+                header[offset],
+                header[offset + 1],
+                header[offset + 2],
+                header[offset + 3],
+            ])
+        };
+
+        let page_size = match u16::from_be_bytes([header[16], header[17]]) {
+            1 => 65536,
+            size => size as u32,
+        };
+
+        if !(512..=65536).contains(&page_size) || !page_size.is_power_of_two() {
+            bail!("Invalid page size {}", page_size);
+        }
+
+        if page_size - (header[20] as u32) < 480 {
+            bail!("Too many reserved bytes per page");
+        }
+
+        if header[21..24] != [64, 32, 32] {
+            bail!("Invalid payload fractions in database header");
+        }
+
+        Ok(DatabaseHeader {
+            page_size,
+            write_version: header[18],
+            read_version: header[19],
+            reserved_bytes: header[20],
+            file_change_counter: read_u32(24),
+            page_count: read_u32(28),
+            freelist_trunk_page: read_u32(32),
+            freelist_count: read_u32(36),
+            schema_cookie: read_u32(40),
+            schema_format: read_u32(44),
+            default_cache_size: read_u32(48),
+            autovacuum_top_root: read_u32(52),
+            text_encoding: TextEncoding::new(read_u32(56))?,
+            user_version: read_u32(60),
+            incremental_vacuum: read_u32(64),
+            application_id: read_u32(68),
+            version_valid_for: read_u32(92),
+            sqlite_version_number: read_u32(96),
+        })
+    }
+
+    /// The in-header database size, which older writers left stale; those
+    /// files are recognized by a `version_valid_for` that doesn't match the
+    /// change counter.
+    pub fn valid_page_count(&self) -> Option<u32> {
+        if self.page_count != 0 && self.version_valid_for == self.file_change_counter {
+            Some(self.page_count)
+        } else {
+            None
+        }
+    }
+
+    pub fn usable_size(&self) -> u32 {
+        self.page_size - self.reserved_bytes as u32
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextEncoding {
+    Utf8 = 1,
+    Utf16le = 2,
+    Utf16be = 3,
+}
+
+impl TextEncoding {
+    fn new(value: u32) -> Result<Self> {
+        match value {
+            // A freshly created, still empty database has no encoding yet.
+            0 | 1 => Ok(TextEncoding::Utf8),
+            2 => Ok(TextEncoding::Utf16le),
+            3 => Ok(TextEncoding::Utf16be),
+            _ => bail!("Invalid text encoding {}", value),
+        }
+    }
+}
+
+impl std::fmt::Display for TextEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextEncoding::Utf8 => write!(f, "{} (utf8)", *self as u8),
+            TextEncoding::Utf16le => write!(f, "{} (utf16le)", *self as u8),
+            TextEncoding::Utf16be => write!(f, "{} (utf16be)", *self as u8),
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use super::database_handler::Database;
+use super::header_handler::DatabaseHeader;
+use super::record_handler::RecordFormat;
+use super::tree_handler::{BTreeCell, BTreePage, BTreePageType};
+use super::vfs_handler::PageBytes;
+
+use anyhow::Result;
+use std::fmt;
+use std::rc::Rc;
+
+const BYTES_PER_LINE: usize = 16;
+
+/// Parsed structure and annotated hex dump of a single page, as printed by
+/// the `.page N` command.
+pub struct PageDump {
+    page_number: u32,
+    raw: PageBytes,
+    page: Result<Rc<BTreePage>>,
+    /// Decoded record of every cell, overflow pages included.
+    values: Vec<Result<Vec<RecordFormat>>>,
+}
+
+impl PageDump {
+    pub fn new(database: &Database, page_number: u32) -> Result<Self> {
+        let page = database.read_page(page_number);
+
+        let values = match &page {
+            Ok(page) => (0..page.cell_count())
+                .filter_map(|i| database.cell(page, i))
+                .map(|cell| match cell? {
+                    BTreeCell::InteriorIndexCell(cell) => Ok(cell.values()?),
+                    BTreeCell::LeafIndexCell(cell) => Ok(cell.values()?),
+                    BTreeCell::LeafTableCell(cell) => Ok(cell.values()?),
+                    BTreeCell::InteriorTableCell(_) => Ok(Vec::new()),
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+
+        Ok(PageDump {
+            page_number,
+            raw: database.read_raw_page(page_number)?,
+            page,
+            values,
+        })
+    }
+
+    /// Labels every byte of the page with the structure it belongs to.
+    fn regions(&self) -> Vec<String> {
+        let mut regions = vec![String::new(); self.raw.len()];
+
+        let header_offset = if self.page_number == 1 {
+            regions[..DatabaseHeader::SIZE].fill(String::from("database header"));
+            DatabaseHeader::SIZE
+        } else {
+            0
+        };
+
+        let Ok(page) = &self.page else {
+            return regions;
+        };
+
+        regions[header_offset..page.usable_size].fill(String::from("unallocated"));
+        regions[page.usable_size..].fill(String::from("reserved"));
+
+        let cell_pointer_start = page.cell_pointer_end() - page.cell_pointers.len() * 2;
+        regions[header_offset..cell_pointer_start].fill(String::from("page header"));
+        regions[cell_pointer_start..page.cell_pointer_end()].fill(String::from("cell pointers"));
+
+        for (i, (offset, cell)) in page.cell_pointers.iter().zip(page.cells()).enumerate() {
+            let Ok(cell) = cell else {
+                continue;
+            };
+
+            let start = *offset as usize;
+            let end = (start + page.cell_size(&cell)).min(page.usable_size);
+            regions[start..end].fill(format!("cell {}", i));
+        }
+
+        for (offset, size) in &page.freeblocks {
+            let start = *offset as usize;
+            regions[start..start + *size as usize].fill(String::from("freeblock"));
+        }
+
+        regions
+    }
+
+    fn fmt_page(&self, f: &mut fmt::Formatter<'_>, page: &BTreePage) -> fmt::Result {
+        let page_type = match page.page_type {
+            BTreePageType::InteriorIndexPage => "interior index",
+            BTreePageType::InteriorTablePage => "interior table",
+            BTreePageType::LeafIndexPage => "leaf index",
+            BTreePageType::LeafTablePage => "leaf table",
+        };
+
+        writeln!(f, "Page {}: {} b-tree page", self.page_number, page_type)?;
+        writeln!(
+            f,
+            "{:<22} {}",
+            "first freeblock:", page.first_freeblock_offset
+        )?;
+        writeln!(f, "{:<22} {}", "number of cells:", page.num_cells)?;
+        writeln!(f, "{:<22} {}", "cell content area:", page.cell_content_area)?;
+        writeln!(f, "{:<22} {}", "fragmented bytes:", page.fragment_bytes)?;
+
+        if let Some(right_most_pointer) = page.right_most_pointer {
+            writeln!(f, "{:<22} {}", "right-most pointer:", right_most_pointer)?;
+        }
+
+        writeln!(f)?;
+
+        for (i, (offset, cell)) in page.cell_pointers.iter().zip(page.cells()).enumerate() {
+            let cell = match cell {
+                Ok(cell) => cell,
+                Err(error) => {
+                    writeln!(f, "Cell {} at offset {}: {}", i, offset, error)?;
+                    continue;
+                }
+            };
+
+            write!(
+                f,
+                "Cell {} at offset {}, {} bytes:",
+                i,
+                offset,
+                page.cell_size(&cell)
+            )?;
+
+            match &cell {
+                BTreeCell::InteriorTableCell(cell) => {
+                    writeln!(
+                        f,
+                        " left child {}, rowid {}",
+                        cell.left_child_page, cell.row_id.value
+                    )?;
+                    continue;
+                }
+                BTreeCell::InteriorIndexCell(cell) => {
+                    write!(f, " left child {},", cell.left_child_page)?;
+                }
+                BTreeCell::LeafTableCell(cell) => {
+                    write!(f, " rowid {},", cell.row_id.value)?;
+                }
+                BTreeCell::LeafIndexCell(_) => {}
+            }
+
+            write!(
+                f,
+                " payload {} bytes",
+                cell.payload_size().unwrap_or_default()
+            )?;
+
+            if let Some(first_overflow_page) = cell.first_overflow_page() {
+                write!(f, ", overflow page {}", first_overflow_page)?;
+            }
+
+            match &self.values[i] {
+                Ok(values) => writeln!(f, "\n    {:?}", values)?,
+                Err(error) => writeln!(f, "\n    {}", error)?,
+            }
+        }
+
+        if !page.freeblocks.is_empty() {
+            writeln!(f)?;
+        }
+
+        for (offset, size) in &page.freeblocks {
+            writeln!(f, "Freeblock at offset {}, {} bytes", offset, size)?;
+        }
+
+        writeln!(f)
+    }
+}
+
+impl fmt::Display for PageDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.page {
+            Ok(page) => self.fmt_page(f, page)?,
+            Err(error) => writeln!(f, "Page {}: {}\n", self.page_number, error)?,
+        }
+
+        let regions = self.regions();
+        let mut skipping = false;
+
+        for (line, bytes) in self.raw.chunks(BYTES_PER_LINE).enumerate() {
+            let offset = line * BYTES_PER_LINE;
+            let mut labels = regions[offset..offset + bytes.len()].to_vec();
+            labels.dedup();
+
+            // Runs of zeroed, unallocated lines collapse into a single `*`.
+            let unallocated = labels == ["unallocated"] || labels == [""];
+            if unallocated && bytes.iter().all(|b| *b == 0) {
+                if !skipping {
+                    writeln!(f, "*")?;
+                }
+                skipping = true;
+                continue;
+            }
+            skipping = false;
+
+            let hex = bytes
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let ascii = bytes
+                .iter()
+                .map(|b| match b {
+                    0x20..=0x7e => *b as char,
+                    _ => '.',
+                })
+                .collect::<String>();
+
+            let line = format!(
+                "{:05x}: {:<47}  {:<16}  {}",
+                offset,
+                hex,
+                ascii,
+                labels.join(", ")
+            );
+            writeln!(f, "{}", line.trim_end())?;
+        }
+
+        Ok(())
+    }
+}
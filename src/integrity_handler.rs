@@ -0,0 +1,535 @@
+use super::database_handler::Database;
+use super::freelist_handler::{FreePage, FreelistIterator};
+use super::header_handler::TextEncoding;
+use super::record_handler::{RecordFormat, SchemaRecord};
+use super::tokenizer::{Token, Tokenizer};
+use super::tree_handler::{BTreeCell, BTreePage, BTreePageType};
+
+use anyhow::{bail, Result};
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+/// Number of errors `PRAGMA integrity_check` reports by default.
+pub const DEFAULT_MAX_ERRORS: usize = 100;
+
+/// The page holding the lock bytes at offset 1 GiB, which is never used.
+const PENDING_BYTE: u64 = 0x4000_0000;
+
+/// Sort key of a b-tree cell.
+#[derive(Clone)]
+enum Key {
+    RowId(i64),
+    Entry(Vec<RecordFormat>),
+}
+
+impl Key {
+    fn compare(&self, other: &Key) -> Ordering {
+        match (self, other) {
+            (Key::RowId(a), Key::RowId(b)) => a.cmp(b),
+            (Key::Entry(a), Key::Entry(b)) => a
+                .iter()
+                .zip(b)
+                .map(|(a, b)| a.compare(b))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or_else(|| a.len().cmp(&b.len())),
+            _ => Ordering::Equal,
+        }
+    }
+}
+
+/// A page of a b-tree being checked, and how far along its cells the check
+/// has come.
+struct PageCheck {
+    page: Rc<BTreePage>,
+    prefix: String,
+    /// Next cell to check, the right-most pointer once past the last one.
+    index: usize,
+    /// Depth of the subtrees checked so far, which must all agree.
+    depth: Option<usize>,
+    /// Key of the last cell checked, or the lower bound of the page.
+    previous: Option<Key>,
+    upper: Option<Key>,
+    /// The cell and key whose left child is being checked.
+    child_of: Option<(usize, Option<Key>)>,
+}
+
+struct Tree {
+    root: u32,
+    table: bool,
+    /// Whether keys can be compared with the BINARY collation, ascending.
+    ordered: bool,
+}
+
+/// Walks the freelist and every b-tree in the schema, checking that each
+/// page is used exactly once and that the b-trees are well formed.
+pub struct IntegrityChecker<'a> {
+    database: &'a Database,
+    page_count: u32,
+    usable_size: usize,
+    referenced: Vec<bool>,
+    errors: Vec<String>,
+    max_errors: usize,
+}
+
+impl<'a> IntegrityChecker<'a> {
+    pub fn new(database: &'a Database, max_errors: usize) -> Self {
+        let page_count = database.page_count();
+
+        IntegrityChecker {
+            database,
+            page_count,
+            usable_size: database.header().usable_size() as usize,
+            referenced: vec![false; page_count as usize + 1],
+            errors: Vec::new(),
+            max_errors,
+        }
+    }
+
+    /// Runs every check, returning `ok` or one message per problem found.
+    pub fn check(mut self, schema_records: &[SchemaRecord]) -> Vec<String> {
+        self.check_freelist();
+
+        self.check_tree(&Tree {
+            root: 1,
+            table: true,
+            ordered: true,
+        });
+
+        for record in schema_records.iter().filter(|r| r.rootpage > 0) {
+            let table = schema_records
+                .iter()
+                .find(|r| r.r#type == "table" && r.name == record.tbl_name);
+            let rowid_table = record.r#type == "table"
+                && !record
+                    .table_definition()
+                    .is_ok_and(|definition| definition.without_rowid);
+
+            self.check_tree(&Tree {
+                root: record.rootpage,
+                table: rowid_table,
+                ordered: rowid_table
+                    || (self.database.header().text_encoding == TextEncoding::Utf8
+                        && record.sql.as_deref().is_none_or(binary_ascending)
+                        && table
+                            .is_none_or(|table| table.sql.as_deref().is_none_or(binary_ascending))),
+            });
+        }
+
+        self.check_unused_pages();
+
+        if self.errors.is_empty() {
+            return vec![String::from("ok")];
+        }
+
+        self.errors[0].insert_str(0, "*** in database main ***\n");
+        self.errors
+    }
+
+    fn error(&mut self, message: String) {
+        if self.errors.len() < self.max_errors {
+            self.errors.push(message);
+        }
+    }
+
+    /// Marks a page as used, failing if it is out of range or already used.
+    fn reference(&mut self, page_number: u32, prefix: &str) -> bool {
+        if page_number == 0 || page_number > self.page_count {
+            self.error(format!("{}invalid page number {}", prefix, page_number));
+            return false;
+        }
+
+        let referenced = &mut self.referenced[page_number as usize];
+
+        if *referenced {
+            self.error(format!("{}2nd reference to page {}", prefix, page_number));
+            return false;
+        }
+
+        *referenced = true;
+        true
+    }
+
+    fn check_freelist(&mut self) {
+        let freelist_count = self.database.header().freelist_count;
+        let mut count = 0;
+
+        for free_page in FreelistIterator::new(self.database) {
+            match free_page {
+                Ok(free_page) => {
+                    count += 1;
+
+                    // Once a trunk page is bad, the rest of the list is too.
+                    if !self.reference(free_page.page_number(), "Freelist: ")
+                        && matches!(free_page, FreePage::Trunk(_))
+                    {
+                        break;
+                    }
+                }
+                Err(error) => {
+                    self.error(format!("Freelist: {}", error));
+                    break;
+                }
+            }
+        }
+
+        if count != freelist_count {
+            self.error(format!(
+                "Freelist: size is {} but should be {}",
+                count, freelist_count
+            ));
+        }
+    }
+
+    /// Checks a b-tree, walking it with an explicit stack so that no file,
+    /// however deep its tree, can exhaust the call stack.
+    fn check_tree(&mut self, tree: &Tree) {
+        let prefix = format!("Tree {}: ", tree.root);
+        let mut stack: Vec<PageCheck> = self
+            .open_page(tree, tree.root, &prefix, None, None)
+            .into_iter()
+            .collect();
+        // Depth of the subtree just checked, for the page it hangs from.
+        let mut returned = None;
+
+        while let Some(check) = stack.last_mut() {
+            if let Some(child_depth) = returned.take() {
+                self.check_depth(&mut check.depth, child_depth, &check.prefix);
+
+                match check.child_of.take() {
+                    Some((index, key)) => self.finish_cell(tree, check, index, key),
+                    None => {
+                        // Back from the right-most child, the page is done.
+                        returned = Some(check.depth.map(|depth| depth + 1));
+                        stack.pop();
+                    }
+                }
+
+                continue;
+            }
+
+            let index = check.index;
+
+            if index == check.page.cell_count() {
+                let Some(right_most_pointer) = check.page.right_most_pointer else {
+                    returned = Some(Some(1));
+                    stack.pop();
+                    continue;
+                };
+
+                let (lower, upper) = (check.previous.clone(), check.upper.clone());
+                let prefix = check.prefix.clone();
+
+                match self.open_page(tree, right_most_pointer, &prefix, lower, upper) {
+                    Some(child) => stack.push(child),
+                    None => returned = Some(None),
+                }
+
+                continue;
+            }
+
+            check.index += 1;
+            let cell_prefix = format!(
+                "Tree {} page {} cell {}: ",
+                tree.root, check.page.page_number, index
+            );
+
+            let cell = match check.page.cell(index) {
+                Some(Ok(cell)) => cell,
+                Some(Err(error)) => {
+                    self.error(format!("{}{}", cell_prefix, error));
+                    continue;
+                }
+                None => continue,
+            };
+
+            let (key, left_child_page) = match &cell {
+                BTreeCell::InteriorTableCell(cell) => (
+                    Ok(Key::RowId(cell.row_id.value)),
+                    Some(cell.left_child_page),
+                ),
+                BTreeCell::LeafTableCell(cell) => (Ok(Key::RowId(cell.row_id.value)), None),
+                BTreeCell::InteriorIndexCell(cell) => (
+                    self.index_key(&check.page, index),
+                    Some(cell.left_child_page),
+                ),
+                BTreeCell::LeafIndexCell(_) => (self.index_key(&check.page, index), None),
+            };
+
+            let key = match key {
+                Ok(key) => Some(key),
+                Err(error) => {
+                    self.error(format!("{}{}", cell_prefix, error));
+                    None
+                }
+            };
+
+            match left_child_page {
+                Some(left_child_page) => {
+                    let (lower, upper) = (check.previous.clone(), key.clone());
+                    check.child_of = Some((index, key));
+
+                    match self.open_page(tree, left_child_page, &cell_prefix, lower, upper) {
+                        Some(child) => stack.push(child),
+                        None => returned = Some(None),
+                    }
+                }
+                None => self.finish_cell(tree, check, index, key),
+            }
+        }
+    }
+
+    /// Starts checking a page whose keys must lie between `lower` and
+    /// `upper`, or reports why it can't be part of the tree.
+    fn open_page(
+        &mut self,
+        tree: &Tree,
+        page_number: u32,
+        reference_prefix: &str,
+        lower: Option<Key>,
+        upper: Option<Key>,
+    ) -> Option<PageCheck> {
+        if !self.reference(page_number, reference_prefix) {
+            return None;
+        }
+
+        let prefix = format!("Tree {} page {}: ", tree.root, page_number);
+
+        let page = match self.database.read_page(page_number) {
+            Ok(page) => page,
+            Err(error) => {
+                self.error(format!("{}{}", prefix, error));
+                return None;
+            }
+        };
+
+        let table_page = matches!(
+            page.page_type,
+            BTreePageType::InteriorTablePage | BTreePageType::LeafTablePage
+        );
+
+        if table_page != tree.table {
+            let kind = if table_page { "table" } else { "index" };
+            self.error(format!("{}unexpected {} page in the b-tree", prefix, kind));
+            return None;
+        }
+
+        self.check_cell_area(&prefix, page_number, &page);
+
+        Some(PageCheck {
+            page,
+            prefix,
+            index: 0,
+            depth: None,
+            previous: lower,
+            upper,
+            child_of: None,
+        })
+    }
+
+    /// Checks what is left of a cell once its left child, if any, is done:
+    /// its overflow chain and the order of its key.
+    fn finish_cell(&mut self, tree: &Tree, check: &mut PageCheck, index: usize, key: Option<Key>) {
+        let cell_prefix = format!(
+            "Tree {} page {} cell {}: ",
+            tree.root, check.page.page_number, index
+        );
+
+        if let Some(Ok(cell)) = check.page.cell(index) {
+            self.check_overflow(&cell_prefix, &check.page, &cell);
+        }
+
+        if let Some(key) = key {
+            if tree.ordered
+                && !in_order(
+                    &key,
+                    check.previous.as_ref(),
+                    check.upper.as_ref(),
+                    tree.table,
+                )
+            {
+                match key {
+                    Key::RowId(row_id) => {
+                        self.error(format!("{}Rowid {} out of order", cell_prefix, row_id))
+                    }
+                    Key::Entry(_) => self.error(format!("{}Key out of order", cell_prefix)),
+                }
+            }
+
+            check.previous = Some(key);
+        }
+    }
+
+    /// Decodes the entry of an index cell, overflow pages included.
+    fn index_key(&self, page: &BTreePage, index: usize) -> Result<Key> {
+        match self.database.cell(page, index).transpose()? {
+            Some(BTreeCell::InteriorIndexCell(cell)) => Ok(Key::Entry(cell.values()?)),
+            Some(BTreeCell::LeafIndexCell(cell)) => Ok(Key::Entry(cell.values()?)),
+            _ => bail!("Table cell in an index b-tree"),
+        }
+    }
+
+    fn check_depth(&mut self, depth: &mut Option<usize>, child_depth: Option<usize>, prefix: &str) {
+        match (*depth, child_depth) {
+            (None, child_depth) => *depth = child_depth,
+            (Some(depth), Some(child_depth)) if depth != child_depth => {
+                self.error(format!("{}Child page depth differs", prefix))
+            }
+            _ => {}
+        }
+    }
+
+    /// Checks that cells and freeblocks fit on the page without overlapping
+    /// and that the remaining gaps add up to the fragmented byte count.
+    fn check_cell_area(&mut self, prefix: &str, page_number: u32, page: &BTreePage) {
+        let usable_size = page.usable_size;
+        let mut used = vec![false; usable_size];
+        let mut valid = true;
+        let mut covered = 0;
+
+        let cells = page
+            .cell_pointers
+            .iter()
+            .zip(page.cells())
+            .filter_map(|(offset, cell)| Some((*offset as usize, page.cell_size(&cell.ok()?))));
+        let freeblocks = page
+            .freeblocks
+            .iter()
+            .map(|(offset, size)| (*offset as usize, *size as usize));
+
+        for (i, (offset, size)) in cells.chain(freeblocks).enumerate() {
+            if offset + size > usable_size {
+                self.error(format!("{}cell {}: Extends off end of page", prefix, i));
+                valid = false;
+                continue;
+            }
+
+            if let Some(byte) = (offset..offset + size).find(|byte| used[*byte]) {
+                if valid {
+                    self.error(format!(
+                        "Multiple uses for byte {} of page {}",
+                        byte, page_number
+                    ));
+                }
+                valid = false;
+            }
+
+            used[offset..offset + size].fill(true);
+            covered += size;
+        }
+
+        // A content area offset of zero stands for 65536.
+        let cell_content_area = match page.cell_content_area {
+            0 => 65536,
+            offset => offset as usize,
+        };
+
+        if valid && cell_content_area <= usable_size {
+            let fragment_bytes = (usable_size - cell_content_area).saturating_sub(covered);
+
+            if fragment_bytes != page.fragment_bytes as usize {
+                self.error(format!(
+                    "Fragmentation of {} bytes reported as {} on page {}",
+                    fragment_bytes, page.fragment_bytes, page_number
+                ));
+            }
+        }
+    }
+
+    fn check_overflow(&mut self, prefix: &str, page: &BTreePage, cell: &BTreeCell) {
+        let (Some(payload_size), Some(first_overflow_page)) =
+            (cell.payload_size(), cell.first_overflow_page())
+        else {
+            return;
+        };
+
+        let overflow_size = payload_size - page.local_payload_size(payload_size);
+        let expected = overflow_size.div_ceil(self.usable_size - 4);
+        let mut next_page = first_overflow_page;
+        let mut count = 0;
+
+        while next_page != 0 && self.reference(next_page, prefix) {
+            count += 1;
+
+            match self.database.read_raw_page(next_page) {
+                Ok(page) => next_page = read_u32(&page, 0),
+                Err(error) => {
+                    self.error(format!("{}{}", prefix, error));
+                    return;
+                }
+            }
+        }
+
+        if next_page == 0 && count != expected {
+            self.error(format!(
+                "{}overflow list length is {} but should be {}",
+                prefix, count, expected
+            ));
+        }
+    }
+
+    fn check_unused_pages(&mut self) {
+        let header = self.database.header();
+        let pending_byte_page = (PENDING_BYTE / header.page_size as u64) as u32 + 1;
+
+        // Auto-vacuum databases interleave pointer map pages with the rest.
+        let pointer_map_page = |page_number: u32| {
+            let pages_per_map = self.usable_size as u32 / 5 + 1;
+            let mut map_page = (page_number - 2) / pages_per_map * pages_per_map + 2;
+
+            if map_page == pending_byte_page {
+                map_page += 1;
+            }
+
+            map_page == page_number
+        };
+
+        let unused = (1..=self.page_count)
+            .filter(|page_number| !self.referenced[*page_number as usize])
+            .filter(|page_number| *page_number != pending_byte_page)
+            .filter(|page_number| {
+                header.autovacuum_top_root == 0
+                    || *page_number < 2
+                    || !pointer_map_page(*page_number)
+            })
+            .collect::<Vec<_>>();
+
+        for page_number in unused {
+            self.error(format!("Page {}: never used", page_number));
+        }
+    }
+}
+
+/// Whether a key lies after `lower` and before `upper`. The keys of a table
+/// b-tree may equal the upper bound, which is the rowid of the parent cell.
+fn in_order(key: &Key, lower: Option<&Key>, upper: Option<&Key>, table: bool) -> bool {
+    let after_lower = lower.is_none_or(|lower| key.compare(lower).is_gt());
+    let before_upper = upper.is_none_or(|upper| match key.compare(upper) {
+        Ordering::Less => true,
+        Ordering::Equal => table,
+        Ordering::Greater => false,
+    });
+
+    after_lower && before_upper
+}
+
+/// Whether an index declared by `sql` sorts with the default BINARY
+/// collation in ascending order, which is the only order checked here.
+fn binary_ascending(sql: &str) -> bool {
+    let mut tokenizer = Tokenizer::new(sql);
+
+    while let Some(token) = tokenizer.next() {
+        if let Token::Text(t) = token {
+            if t.eq_ignore_ascii_case("collate") || t.eq_ignore_ascii_case("desc") {
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+fn read_u32(page: &[u8], offset: usize) -> u32 {
+    page.get(offset..offset + 4).map_or(0, |bytes| {
+        u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    })
+}
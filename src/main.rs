@@ -1,478 +1,151 @@
+mod cache_handler;
+mod database_handler;
+mod error_handler;
+mod freelist_handler;
+mod graph_handler;
+mod header_handler;
+mod inspect_handler;
+mod integrity_handler;
+mod mmap_handler;
 mod record_handler;
+mod schema_handler;
 mod sql_handler;
 mod tokenizer;
 mod tree_handler;
+mod vfs_handler;
+mod wal_handler;
 
 use anyhow::{anyhow, bail, Result};
-use std::cell::RefCell;
-use std::rc::Rc;
-use std::vec::IntoIter;
 
-use crate::record_handler::{RecordFormat, SchemaRecord};
-use crate::sql_handler::{
-    MappedSqlColumnVariant, MappedSqlStatement, MappedSqlWhereClause, SqlStatement,
-};
-use crate::tree_handler::{BTreeCell, BTreeLeafTableCell, BTreePage, PageReader};
+use crate::database_handler::{Database, Record};
+use crate::freelist_handler::FreelistIterator;
+use crate::graph_handler::BTreeGraph;
+use crate::inspect_handler::PageDump;
+use crate::sql_handler::Statement;
+use crate::vfs_handler::{MemorySource, PageAccess};
 
-static mut PAGE_READER: Option<PageReader> = None;
-
-pub fn read_page(page_num: u32) -> Result<BTreePage> {
-    if let Some(page_reader) = unsafe { &mut PAGE_READER } {
-        page_reader.read_page(page_num)
-    } else {
-        panic!("page reader not initialized")
-    }
-}
-
-fn init_global_page_reader(file_name: &str) -> Result<()> {
-    unsafe {
-        PAGE_READER = Some(PageReader::new(file_name)?);
-    }
-
-    Ok(())
-}
-
-pub struct CellIterator {
-    cell_iter: IntoIter<BTreeCell>,
-    right_most_pointer: Option<u32>,
-    record_iter: Option<Box<CellIterator>>,
-    get_index: usize,
-    flag: bool,
-}
+fn main() -> Result<()> {
+    let mut args = std::env::args().collect::<Vec<_>>();
 
-impl CellIterator {
-    pub fn new(page: BTreePage) -> Result<Self> {
-        Ok(CellIterator {
-            cell_iter: page.cells.into_iter(),
-            right_most_pointer: page.right_most_pointer,
-            record_iter: None,
-            get_index: 0,
-            flag: false,
-        })
+    let mut access = PageAccess::File;
+    if let Some(position) = args.iter().position(|a| a == "--mmap") {
+        args.remove(position);
+        access = PageAccess::Mmap;
     }
 
-    fn get(&mut self, index: usize) -> Option<BTreeLeafTableCell> {
-        if let Some(records) = &mut self.record_iter {
-            let record = records.get(index);
-
-            if record.is_some() {
-                return record;
-            }
-        }
-
-        self.get_index = index;
-        let value = self.next();
-        self.get_index = 0;
-
-        value
+    match args.len() {
+        0 | 1 => bail!("Missing <database path> and <command>"),
+        2 => bail!("Missing <command>"),
+        _ => {}
     }
-}
-
-impl Iterator for CellIterator {
-    type Item = BTreeLeafTableCell;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            if self.flag {
-                return None;
-            }
-
-            if let Some(records) = &mut self.record_iter {
-                let record = records.next();
-
-                if record.is_some() {
-                    return record;
-                }
-            }
-
-            if let Some(cell) = self.cell_iter.next() {
-                match cell {
-                    BTreeCell::LeafTableCell(cell) => {
-                        if self.get_index > 0 && cell.row_id.value < self.get_index as i64 {
-                            continue;
-                        }
 
-                        if self.get_index > cell.row_id.value as usize {
-                            return None;
-                        }
-
-                        return Some(cell);
+    // A database path of `-` reads the whole database from stdin.
+    let database = match args[1].as_str() {
+        "-" => Database::from_source(Box::new(MemorySource::from_stdin()?))?,
+        file_name => Database::open(file_name, access)?,
+    };
+    let schema = &database.schema;
+
+    // Commands run in order, so settings like `PRAGMA cache_size` apply to
+    // the queries that follow them.
+    for command in &args[2..] {
+        match command.as_str() {
+            ".dbinfo" => {
+                let header = database.header();
+
+                let file_size = database.page_count() as u64 * header.page_size as u64;
+
+                // A damaged freelist only costs the line that depends on it,
+                // the rest comes from the header.
+                let wasted = match FreelistIterator::new(&database).collect::<Result<Vec<_>>>() {
+                    Ok(free_pages) => {
+                        let wasted = free_pages.len() as u64 * header.page_size as u64;
+
+                        format!(
+                            "{} bytes ({:.1}%)",
+                            wasted,
+                            wasted as f64 * 100.0 / file_size.max(1) as f64
+                        )
                     }
-                    BTreeCell::InteriorTableCell(cell) => {
-                        if self.get_index > 0 && cell.row_id.value < self.get_index as i64 {
-                            continue;
-                        }
-
-                        let b_tree_page = read_page(cell.left_child_page).unwrap();
-
-                        self.record_iter = Some(Box::new(CellIterator {
-                            cell_iter: b_tree_page.cells.into_iter(),
-                            right_most_pointer: b_tree_page.right_most_pointer,
-                            record_iter: None,
-                            get_index: self.get_index,
-                            flag: false,
-                        }));
-
-                        self.get_index = 0;
-
-                        continue;
-                    }
-                    _ => panic!("Records::next: cell is not table cell"),
-                }
-            } else {
-                if let Some(right_most_pointer) = self.right_most_pointer {
-                    let b_tree_page = read_page(right_most_pointer).unwrap();
-
-                    self.record_iter = Some(Box::new(CellIterator {
-                        cell_iter: b_tree_page.cells.into_iter(),
-                        right_most_pointer: b_tree_page.right_most_pointer,
-                        record_iter: None,
-                        get_index: self.get_index,
-                        flag: false,
-                    }));
-
-                    self.right_most_pointer = None;
-                    continue;
+                    Err(error) => format!("unknown ({})", error),
+                };
+                let fields = [
+                    ("database page size:", header.page_size.to_string()),
+                    ("write format:", header.write_version.to_string()),
+                    ("read format:", header.read_version.to_string()),
+                    ("reserved bytes:", header.reserved_bytes.to_string()),
+                    (
+                        "file change counter:",
+                        header.file_change_counter.to_string(),
+                    ),
+                    ("database page count:", header.page_count.to_string()),
+                    ("freelist page count:", header.freelist_count.to_string()),
+                    ("wasted space:", wasted),
+                    ("schema cookie:", header.schema_cookie.to_string()),
+                    ("schema format:", header.schema_format.to_string()),
+                    ("default cache size:", header.default_cache_size.to_string()),
+                    (
+                        "autovacuum top root:",
+                        header.autovacuum_top_root.to_string(),
+                    ),
+                    ("incremental vacuum:", header.incremental_vacuum.to_string()),
+                    ("text encoding:", header.text_encoding.to_string()),
+                    ("user version:", header.user_version.to_string()),
+                    ("application id:", header.application_id.to_string()),
+                    (
+                        "software version:",
+                        header.sqlite_version_number.to_string(),
+                    ),
+                    ("number of tables:", schema.count("table").to_string()),
+                    ("number of indexes:", schema.count("index").to_string()),
+                    ("number of triggers:", schema.count("trigger").to_string()),
+                    ("number of views:", schema.count("view").to_string()),
+                    ("schema size:", schema.size().to_string()),
+                    // The sqlite3 shell prints this one without a colon.
+                    ("data version", "1".to_string()),
+                ];
+
+                for (name, value) in fields {
+                    println!("{:<20} {}", name, value);
                 }
             }
-
-            self.flag = true;
-        }
-    }
-}
-
-struct IndexIterator {
-    cell_iter: IntoIter<BTreeCell>,
-    right_most_pointer: Option<u32>,
-    index_iter: Option<Box<IndexIterator>>,
-    record_iter: Rc<RefCell<CellIterator>>,
-    keys: Rc<Vec<String>>,
-    flag: bool,
-}
-
-impl IndexIterator {
-    fn new(page: BTreePage, cell_iter: CellIterator, keys: Vec<String>) -> Result<Self> {
-        Ok(IndexIterator {
-            cell_iter: page.cells.into_iter(),
-            index_iter: None,
-            right_most_pointer: page.right_most_pointer,
-            record_iter: Rc::new(RefCell::new(cell_iter)),
-            keys: Rc::new(keys),
-            flag: false,
-        })
-    }
-}
-
-impl Iterator for IndexIterator {
-    type Item = BTreeLeafTableCell;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        'outer: loop {
-            if self.flag {
-                return None;
-            }
-
-            if let Some(indices) = &mut self.index_iter {
-                let index = indices.next();
-
-                if index.is_some() {
-                    return index;
+            ".tables" => {
+                for table in schema.tables() {
+                    println!("{}", table.name);
                 }
             }
-
-            if let Some(cell) = self.cell_iter.next() {
-                match cell {
-                    BTreeCell::LeafIndexCell(cell) => {
-                        let mut values = cell.values().unwrap();
-                        let row_id = values.pop().unwrap().into();
-
-                        for i in 0..self.keys.len() {
-                            if String::from(&values[i]) != self.keys[i] {
-                                continue 'outer;
-                            }
-                        }
-
-                        let cell = self.record_iter.borrow_mut().get(row_id);
-
-                        if cell.is_some() {
-                            return cell;
-                        }
-
-                        continue;
-                    }
-                    BTreeCell::InteriorIndexCell(cell) => {
-                        let b_tree_page = read_page(cell.left_child_page).unwrap();
-                        let mut values = cell.values().unwrap();
-                        values.pop().unwrap();
-
-                        for i in 0..self.keys.len() {
-                            if &String::from(&values[i]) < &self.keys[i] {
-                                continue 'outer;
-                            }
-                        }
-
-                        self.index_iter = Some(Box::new(IndexIterator {
-                            cell_iter: b_tree_page.cells.into_iter(),
-                            right_most_pointer: b_tree_page.right_most_pointer,
-                            keys: self.keys.clone(),
-                            index_iter: None,
-                            record_iter: self.record_iter.clone(),
-                            flag: false,
-                        }));
-
-                        continue;
-                    }
-                    _ => panic!("Records::next: cell is not index cell"),
-                }
-            } else {
-                if let Some(right_most_pointer) = self.right_most_pointer {
-                    let b_tree_page = read_page(right_most_pointer).unwrap();
-
-                    self.index_iter = Some(Box::new(IndexIterator {
-                        cell_iter: b_tree_page.cells.into_iter(),
-                        right_most_pointer: b_tree_page.right_most_pointer,
-                        keys: self.keys.clone(),
-                        index_iter: None,
-                        record_iter: self.record_iter.clone(),
-                        flag: false,
-                    }));
-
-                    self.right_most_pointer = None;
-                    continue;
-                }
-            }
-
-            self.flag = true;
-        }
-    }
-}
-
-struct FilteredRecords {
-    record_iter: Box<dyn Iterator<Item = BTreeLeafTableCell>>,
-    mapped_sql_where_clause: MappedSqlWhereClause,
-}
-
-impl FilteredRecords {
-    fn new(
-        record_iter: Box<dyn Iterator<Item = BTreeLeafTableCell>>,
-        mapped_sql_where_clause: MappedSqlWhereClause,
-    ) -> Self {
-        FilteredRecords {
-            record_iter,
-            mapped_sql_where_clause,
-        }
-    }
-}
-
-impl Iterator for FilteredRecords {
-    type Item = Record;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            let cell = self.record_iter.next()?;
-            let record = Record::from(cell);
-
-            if self.mapped_sql_where_clause.matches(&record) {
-                return Some(record);
+            ".stats" => {
+                println!("{}", database.cache_stats());
             }
-        }
-    }
-}
+            command if command.starts_with(".graph") => {
+                let name = command[".graph".len()..].trim();
 
-pub struct Records {
-    record_iter: Box<dyn Iterator<Item = Record>>,
-    column_variants: Vec<MappedSqlColumnVariant>,
-}
-
-impl Records {
-    fn new(
-        cell_iter: Box<dyn Iterator<Item = BTreeLeafTableCell>>,
-        sql_statement: MappedSqlStatement,
-    ) -> Self {
-        let mut record_iter: Box<dyn Iterator<Item = Record>> =
-            if let Some(where_clause) = sql_statement.where_clause {
-                Box::new(FilteredRecords::new(cell_iter, where_clause))
-            } else {
-                Box::new(cell_iter.map(|cell| Record::from(cell)))
-            };
-
-        match sql_statement.column_variants[0] {
-            MappedSqlColumnVariant::Count => {
-                let record_count = Record {
-                    values: vec![RecordFormat::Integer64(record_iter.count() as i64)],
-                };
-                record_iter = Box::new(std::iter::once(record_count));
+                print!(
+                    "{}",
+                    BTreeGraph::new(&database, name, schema.rootpage(name)?)?
+                );
             }
-            _ => {}
-        }
-
-        Records {
-            record_iter,
-            column_variants: sql_statement.column_variants,
-        }
-    }
-}
-
-impl Iterator for Records {
-    type Item = Record;
+            command if command.starts_with(".page") => {
+                let page_number = command[".page".len()..]
+                    .trim()
+                    .parse()
+                    .map_err(|_| anyhow!("Usage: .page <page number>"))?;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let mut values = Vec::new();
-        let record_values = self.record_iter.next()?.values;
-
-        for c in &self.column_variants {
-            match c {
-                MappedSqlColumnVariant::Column(index) => {
-                    values.push(record_values[*index].clone());
-                }
-                MappedSqlColumnVariant::EveryColumn => {
-                    values.extend(record_values.iter().map(|c| c.clone()));
-                }
-                MappedSqlColumnVariant::Count => {
-                    values.push(record_values[0].clone());
-                }
+                print!("{}", PageDump::new(&database, page_number)?);
             }
-        }
-
-        Some(Record { values })
-    }
-}
-
-#[derive(Debug)]
-pub struct Record {
-    values: Vec<RecordFormat>,
-}
-
-impl From<BTreeLeafTableCell> for Record {
-    fn from(cell: BTreeLeafTableCell) -> Self {
-        let mut values = cell.values().unwrap();
-
-        if values[0] == RecordFormat::NULL {
-            values[0] = RecordFormat::Integer64(cell.row_id.value);
-        }
-
-        Record { values }
-    }
-}
-
-impl std::fmt::Display for Record {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut values = self.values.iter();
-        if let Some(value) = values.next() {
-            write!(f, "{}", String::from(value))?;
-        }
-
-        for value in values {
-            write!(f, "|{}", String::from(value))?;
-        }
-
-        Ok(())
-    }
-}
-
-struct Schema {
-    schema_records: Vec<SchemaRecord>,
-}
-
-impl<'a> Schema {
-    fn new() -> Result<Self> {
-        Ok(Schema {
-            schema_records: read_page(1)?
-                .iter()?
-                .map(|r| SchemaRecord::from(r))
-                .collect(),
-        })
-    }
-
-    fn records(&self, sql_statement: SqlStatement) -> Result<Records> {
-        let table = self
-            .schema_records
-            .iter()
-            .find(|r| r.tbl_name == sql_statement.table_name)
-            .ok_or_else(|| anyhow!("Table '{}' not found", sql_statement.table_name))?;
-
-        let cell_iter = CellIterator::new(read_page(table.rootpage)?)?;
-
-        if sql_statement.where_clause.is_some() {
-            let index = self
-                .schema_records
-                .iter()
-                .filter(|r| r.tbl_name == sql_statement.table_name && r.r#type == "index")
-                .map(|r| {
-                    let mapped_sql_statement = sql_statement.map(&table, Some(r));
-
-                    let mut keys = Vec::new();
-
-                    if mapped_sql_statement.is_ok() {
-                        keys = mapped_sql_statement
-                            .as_ref()
-                            .unwrap()
-                            .where_clause
-                            .as_ref()
-                            .unwrap()
-                            .keys();
+            sql => {
+                let records: Box<dyn Iterator<Item = Result<Record>>> = match Statement::new(sql)? {
+                    Statement::Select(sql_statement) => Box::new(database.records(sql_statement)?),
+                    Statement::Pragma(pragma) => {
+                        Box::new(database.pragma(&pragma)?.into_iter().map(Ok))
                     }
+                };
 
-                    (r.rootpage, mapped_sql_statement, keys)
-                })
-                .max_by_key(|(_, _, keys)| keys.len());
-
-            if let Some(index) = index {
-                let (rootpage, mapped_sql_statement, keys) = index;
-
-                if keys.len() > 0 {
-                    let index = read_page(rootpage)?;
-                    let index_iter = IndexIterator::new(index, cell_iter, keys)?;
-
-                    return Ok(Records::new(Box::new(index_iter), mapped_sql_statement?));
+                for r in records {
+                    println!("{}", r?);
                 }
             }
         }
-
-        let mapped_sql_statement = sql_statement.map(&table, None)?;
-        Ok(Records::new(Box::new(cell_iter), mapped_sql_statement))
-    }
-
-    fn tables(&self) -> impl Iterator<Item = &SchemaRecord> {
-        self.schema_records.iter().filter(|r| r.r#type == "table")
-    }
-}
-
-fn main() -> Result<()> {
-    let args = std::env::args().collect::<Vec<_>>();
-    match args.len() {
-        0 | 1 => bail!("Missing <database path> and <command>"),
-        2 => bail!("Missing <command>"),
-        _ => {}
-    }
-
-    let command = &args[2];
-    init_global_page_reader(&args[1])?;
-
-    let schema = Schema::new()?;
-
-    match command.as_str() {
-        ".dbinfo" => {
-            let page_size = unsafe {
-                PAGE_READER
-                    .as_ref()
-                    .expect("PAGE_READER should be initialized before any operation.")
-                    .page_size()
-            };
-            println!("database page size: {}", page_size);
-            println!("number of tables: {}", schema.tables().count());
-        }
-        ".tables" => {
-            for table in schema.tables() {
-                println!("{}", table.name);
-            }
-        }
-        sql => {
-            let sql_statement = SqlStatement::new(sql)?;
-            for r in schema.records(sql_statement)? {
-                println!("{}", r);
-            }
-        }
     }
 
     Ok(())
@@ -1,7 +1,14 @@
-use super::tokenizer::{Token, Tokenizer};
-use super::BTreeLeafTableCell;
-
-use anyhow::{bail, Result};
+use super::error_handler::{DecodeError, DecodeErrorKind};
+use super::header_handler::TextEncoding;
+use super::schema_handler::{IndexDefinition, TableDefinition, ViewDefinition};
+use super::tokenizer::Token;
+use super::tree_handler::BTreeLeafTableCell;
+
+use anyhow::{anyhow, bail, Result};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt::{self, Write};
+use std::sync::LazyLock;
 
 #[derive(Debug, Clone)]
 pub struct SchemaRecord {
@@ -9,199 +16,192 @@ pub struct SchemaRecord {
     pub name: String,
     pub tbl_name: String,
     pub rootpage: u32,
-    pub sql: String,
+    /// NULL for the indexes sqlite creates for PRIMARY KEY and UNIQUE
+    /// constraints.
+    pub sql: Option<String>,
+    /// The parsed SQL of a table, or why it couldn't be parsed, so that a
+    /// table this reader doesn't understand only fails once it is read.
+    table_definition: Result<TableDefinition, String>,
 }
 
-impl From<BTreeLeafTableCell> for SchemaRecord {
-    fn from(cell: BTreeLeafTableCell) -> Self {
-        let mut cell_iter = cell.values().unwrap().into_iter();
+impl TryFrom<&BTreeLeafTableCell<'_>> for SchemaRecord {
+    type Error = DecodeError;
 
-        let r#type = match cell_iter.next() {
-            Some(RecordFormat::String(s)) => s,
-            _ => panic!("Invalid record format"),
-        };
+    fn try_from(cell: &BTreeLeafTableCell<'_>) -> Result<Self, Self::Error> {
+        let invalid = || cell.error(DecodeErrorKind::InvalidSchemaRecord);
+        let mut cell_iter = cell.values()?.into_iter();
 
-        let name = match cell_iter.next() {
-            Some(RecordFormat::String(s)) => s,
-            _ => panic!("Invalid record format"),
+        let mut text = || match cell_iter.next() {
+            Some(RecordFormat::String(s)) => Ok(s),
+            _ => Err(invalid()),
         };
 
-        let tbl_name = match cell_iter.next() {
-            Some(RecordFormat::String(s)) => s,
-            _ => panic!("Invalid record format"),
-        };
+        let r#type = text()?;
+        let name = text()?;
+        let tbl_name = text()?;
 
-        let rootpage = match cell_iter.next() {
-            Some(RecordFormat::Integer8(i)) => i as i64,
-            Some(RecordFormat::Integer16(i)) => i as i64,
-            Some(RecordFormat::Integer24(i)) => i as i64,
-            Some(RecordFormat::Integer48(i)) => i as i64,
-            Some(RecordFormat::Integer64(i)) => i as i64,
-            _ => panic!("Invalid record format"),
-        };
+        let rootpage = cell_iter
+            .next()
+            .and_then(|rootpage| rootpage.as_i64())
+            .and_then(|rootpage| u32::try_from(rootpage).ok())
+            .ok_or_else(invalid)?;
 
         let sql = match cell_iter.next() {
-            Some(RecordFormat::String(s)) => s,
-            _ => panic!("Invalid record format"),
+            Some(RecordFormat::String(s)) => Some(s),
+            Some(RecordFormat::NULL) => None,
+            _ => return Err(invalid()),
         };
 
-        SchemaRecord {
-            r#type: r#type.to_string(),
-            name: name.to_string(),
-            tbl_name: tbl_name.to_string(),
-            rootpage: rootpage as u32,
-            sql: sql.to_string(),
-        }
+        Ok(SchemaRecord::new(r#type, name, tbl_name, rootpage, sql))
     }
 }
 
 impl SchemaRecord {
-    pub fn table_column_names(&self) -> Result<Vec<String>> {
-        let mut tokenizer = Tokenizer::new(&self.sql);
-
-        tokenizer.tag("CREATE")?;
-        tokenizer.tag("TABLE")?;
-
-        match tokenizer.next() {
-            Some(Token::Text(s)) => {
-                if s != self.name {
-                    bail!("Invalid SQL create statement");
-                }
-            }
-            Some(Token::String(s)) => {
-                if s != self.name {
-                    bail!("Invalid SQL create statement");
-                }
-            }
-            _ => bail!("Invalid SQL create statement"),
-        }
-
-        tokenizer.tag("(")?;
-
-        let mut result = Vec::new();
-
-        loop {
-            let tokens = tokenizer
-                .take_while(|t| *t != Token::Punctuation(',') && *t != Token::Punctuation(')'));
-
-            if tokens.len() < 1 {
-                bail!("Invalid SQL create statement");
-            }
-
-            let token = match tokens.into_iter().next() {
-                Some(Token::Text(s)) => s,
-                Some(Token::String(s)) => s,
-                _ => bail!("Invalid SQL create statement"),
-            };
-
-            result.push(token);
+    fn new(
+        r#type: String,
+        name: String,
+        tbl_name: String,
+        rootpage: u32,
+        sql: Option<String>,
+    ) -> Self {
+        let table_definition = if r#type == "table" {
+            Self::parse_table_definition(&name, sql.as_deref()).map_err(|error| error.to_string())
+        } else {
+            Err(format!("{} is not a table", name))
+        };
 
-            match tokenizer.next() {
-                Some(Token::Punctuation(')')) => break Ok(result),
-                _ => (),
-            }
+        SchemaRecord {
+            r#type,
+            name,
+            tbl_name,
+            rootpage,
+            sql,
+            table_definition,
         }
     }
 
-    pub fn index_columns(&self) -> Result<Vec<String>> {
-        let mut tokenizer = Tokenizer::new(&self.sql);
+    /// The table stored in page 1, whose rows are the schema records.
+    pub fn schema_table() -> &'static SchemaRecord {
+        static SCHEMA_TABLE: LazyLock<SchemaRecord> = LazyLock::new(|| {
+            SchemaRecord::new(
+                "table".to_string(),
+                "sqlite_schema".to_string(),
+                "sqlite_schema".to_string(),
+                1,
+                Some(
+                    "CREATE TABLE sqlite_schema(type text, name text, tbl_name text, rootpage integer, sql text)"
+                        .to_string(),
+                ),
+            )
+        });
+
+        &SCHEMA_TABLE
+    }
 
-        tokenizer.tag("CREATE")?;
-        tokenizer.tag("INDEX")?;
+    fn parse_table_definition(name: &str, sql: Option<&str>) -> Result<TableDefinition> {
+        let sql = sql.ok_or_else(|| anyhow!("Table {} has no SQL create statement", name))?;
+        let definition = TableDefinition::new(sql)?;
 
-        match tokenizer.next() {
-            Some(Token::Text(s)) => {
-                if s != self.name {
-                    bail!("Invalid SQL create statement");
-                }
-            }
-            Some(Token::String(s)) => {
-                if s != self.name {
-                    bail!("Invalid SQL create statement");
-                }
-            }
-            _ => bail!("Invalid SQL create statement"),
+        if definition.name != name {
+            bail!("Invalid SQL create statement");
         }
 
-        tokenizer.tag("ON")?;
+        Ok(definition)
+    }
 
-        match tokenizer.next() {
-            Some(Token::Text(s)) => {
-                if s != self.tbl_name {
-                    bail!("Invalid SQL create statement");
-                }
-            }
-            Some(Token::String(s)) => {
-                if s != self.tbl_name {
-                    bail!("Invalid SQL create statement");
-                }
-            }
-            _ => bail!("Invalid SQL create statement"),
-        }
+    /// The parsed SQL of a table.
+    pub fn table_definition(&self) -> Result<&TableDefinition> {
+        self.table_definition
+            .as_ref()
+            .map_err(|error| anyhow!("{}", error))
+    }
 
-        tokenizer.tag("(")?;
+    pub fn view_definition(&self) -> Result<ViewDefinition> {
+        let sql = self
+            .sql
+            .as_deref()
+            .ok_or_else(|| anyhow!("View {} has no SQL create statement", self.name))?;
+        let definition = ViewDefinition::new(sql)?;
 
-        let mut result = Vec::new();
+        if definition.name != self.name {
+            bail!("Invalid SQL create statement");
+        }
 
-        loop {
-            let tokens = tokenizer
-                .take_while(|t| *t != Token::Punctuation(',') && *t != Token::Punctuation(')'));
+        Ok(definition)
+    }
 
-            if tokens.len() < 1 {
-                bail!("Invalid SQL create statement");
+    /// Definition of an index on `table`. Indexes sqlite creates for a
+    /// constraint have no SQL and are rebuilt from the table's definition.
+    pub fn index_definition(&self, table: &TableDefinition) -> Result<IndexDefinition> {
+        let definition = match &self.sql {
+            Some(sql) => IndexDefinition::new(sql)?,
+            None => {
+                let number = self
+                    .name
+                    .strip_prefix("sqlite_autoindex_")
+                    .and_then(|name| name.strip_prefix(table.name.as_str()))
+                    .and_then(|name| name.strip_prefix('_'))
+                    .and_then(|number| number.parse().ok())
+                    .ok_or_else(|| anyhow!("Index {} has no SQL create statement", self.name))?;
+
+                table.autoindex(&self.name, number)?
             }
+        };
 
-            let token = match tokens.into_iter().next() {
-                Some(Token::Text(s)) => s,
-                Some(Token::String(s)) => s,
-                _ => bail!("Invalid SQL create statement"),
-            };
-
-            result.push(token);
-
-            match tokenizer.next() {
-                Some(Token::Punctuation(')')) => break Ok(result),
-                _ => (),
-            }
+        if definition.name != self.name || !definition.table.eq_ignore_ascii_case(&table.name) {
+            bail!("Invalid SQL create statement");
         }
+
+        Ok(definition)
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Varint {
     pub value: i64,
     pub size: u8,
 }
 
 impl Varint {
-    pub fn from(buf: &[u8]) -> (Self, &[u8]) {
-        if buf.is_empty() {
-            panic!("Varint::from: buf is empty");
-        }
-
+    /// Decodes a varint of up to nine bytes, the last of which contributes
+    /// all eight of its bits.
+    pub fn from(buf: &[u8]) -> Result<(Self, &[u8]), DecodeErrorKind> {
         let mut result = 0;
-        let mut size = 0;
 
-        for byte in buf {
+        for (i, byte) in buf.iter().enumerate() {
+            if i == 8 {
+                result = (result << 8) | *byte as i64;
+
+                return Ok((
+                    Varint {
+                        value: result,
+                        size: 9,
+                    },
+                    &buf[9..],
+                ));
+            }
+
             result = (result << 7) | (byte & 0x7f) as i64;
-            size += 1;
 
             if byte & 0x80 == 0 {
-                break;
+                let size = i + 1;
+
+                return Ok((
+                    Varint {
+                        value: result,
+                        size: size as u8,
+                    },
+                    &buf[size..],
+                ));
             }
         }
 
-        (
-            Varint {
-                value: result,
-                size: size as u8,
-            },
-            &buf[size..],
-        )
+        Err(DecodeErrorKind::UnexpectedEnd)
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum RecordFormat {
     NULL,
     Integer8(i8),
@@ -218,83 +218,233 @@ pub enum RecordFormat {
 }
 
 impl RecordFormat {
-    pub fn new(payload: &[u8], value: i64) -> Result<(Self, &[u8])> {
-        match value {
-            0 => Ok((RecordFormat::NULL, payload)),
-            1 => {
-                let buf: [u8; 1] = [payload[0]];
-                Ok((
-                    RecordFormat::Integer8(i8::from_be_bytes(buf)),
-                    &payload[1..],
-                ))
-            }
-            2 => {
-                let buf: [u8; 2] = [payload[0], payload[1]];
-                Ok((
-                    RecordFormat::Integer16(i16::from_be_bytes(buf)),
-                    &payload[2..],
-                ))
-            }
-            3 => {
-                let buf: [u8; 4] = [0, payload[0], payload[1], payload[2]];
-                Ok((
-                    RecordFormat::Integer24(i32::from_be_bytes(buf)),
-                    &payload[3..],
-                ))
-            }
-            4 => {
-                let buf: [u8; 4] = [payload[0], payload[1], payload[2], payload[3]];
-                Ok((
-                    RecordFormat::Integer32(i32::from_be_bytes(buf)),
-                    &payload[4..],
-                ))
+    pub fn new(
+        payload: &[u8],
+        value: i64,
+        text_encoding: TextEncoding,
+    ) -> Result<(Self, &[u8]), DecodeErrorKind> {
+        RecordValue::new(payload, value, text_encoding)
+            .map(|(value, remaining)| (value.into_owned(), remaining))
+    }
+
+    /// Number of body bytes a value of the given serial type takes up.
+    pub fn size(serial_type: i64) -> Result<usize, DecodeErrorKind> {
+        match serial_type {
+            0 | 8 | 9 => Ok(0),
+            1..=4 => Ok(serial_type as usize),
+            5 => Ok(6),
+            6 | 7 => Ok(8),
+            10 | 11 => Err(DecodeErrorKind::InvalidSerialType(serial_type)),
+            _ if serial_type < 0 => Err(DecodeErrorKind::InvalidSerialType(serial_type)),
+            _ => Ok((serial_type as usize - 12) / 2),
+        }
+    }
+}
+
+/// A value borrowing its text or blob from the payload it was decoded
+/// from, for comparisons that shouldn't copy every value they look at.
+#[derive(Debug, PartialEq, Clone)]
+pub enum RecordValue<'a> {
+    /// Any value other than text or a blob.
+    Scalar(RecordFormat),
+    Blob(&'a [u8]),
+    /// Text, borrowed unless it had to be converted from UTF-16 or had
+    /// invalid UTF-8 replaced.
+    String(Cow<'a, str>),
+}
+
+impl<'a> RecordValue<'a> {
+    pub fn new(
+        payload: &'a [u8],
+        value: i64,
+        text_encoding: TextEncoding,
+    ) -> Result<(Self, &'a [u8]), DecodeErrorKind> {
+        let size = RecordFormat::size(value)?;
+
+        let (buf, remaining) = payload
+            .split_at_checked(size)
+            .ok_or(DecodeErrorKind::UnexpectedEnd)?;
+
+        // Sign-extends a big-endian integer of up to eight bytes.
+        let integer = || {
+            let mut bytes = [if buf[0] & 0x80 == 0 { 0 } else { 0xff }; 8];
+            bytes[8 - size..].copy_from_slice(buf);
+            i64::from_be_bytes(bytes)
+        };
+
+        let record_value = match value {
+            _ if value >= 12 && value % 2 == 0 => RecordValue::Blob(buf),
+            _ if value >= 12 => RecordValue::String(decode_text(buf, text_encoding)),
+            _ => RecordValue::Scalar(match value {
+                0 => RecordFormat::NULL,
+                1 => RecordFormat::Integer8(integer() as i8),
+                2 => RecordFormat::Integer16(integer() as i16),
+                3 => RecordFormat::Integer24(integer() as i32),
+                4 => RecordFormat::Integer32(integer() as i32),
+                5 => RecordFormat::Integer48(integer()),
+                6 => RecordFormat::Integer64(integer()),
+                7 => RecordFormat::Float64(f64::from_bits(integer() as u64)),
+                8 => RecordFormat::Integer0,
+                _ => RecordFormat::Integer1,
+            }),
+        };
+
+        Ok((record_value, remaining))
+    }
+
+    /// Copies the value out of the payload.
+    pub fn into_owned(self) -> RecordFormat {
+        match self {
+            RecordValue::Scalar(value) => value,
+            RecordValue::Blob(b) => RecordFormat::Blob(b.to_vec()),
+            RecordValue::String(s) => RecordFormat::String(s.into_owned()),
+        }
+    }
+
+    /// Orders the value against a key from a where clause, following
+    /// sqlite's sort order of NULLs, then numbers, then text, then blobs.
+    /// Text compares by its bytes in `text_encoding`, as BINARY does.
+    pub fn compare_key(&self, key: &str, text_encoding: TextEncoding) -> Ordering {
+        match self {
+            RecordValue::String(s) => compare_text(s, key, text_encoding),
+            RecordValue::Blob(_) => Ordering::Greater,
+            RecordValue::Scalar(RecordFormat::NULL) if key == "NULL" => Ordering::Equal,
+            RecordValue::Scalar(RecordFormat::NULL) => Ordering::Less,
+            RecordValue::Scalar(RecordFormat::Float64(f)) => match key.parse::<f64>() {
+                Ok(k) => f.partial_cmp(&k).unwrap_or(Ordering::Less),
+                Err(_) => Ordering::Less,
+            },
+            RecordValue::Scalar(value) => {
+                let i = value.as_i64().unwrap_or_default();
+
+                match (key.parse::<i64>(), key.parse::<f64>()) {
+                    (Ok(k), _) => i.cmp(&k),
+                    (_, Ok(k)) => (i as f64).partial_cmp(&k).unwrap_or(Ordering::Less),
+                    _ => Ordering::Less,
+                }
             }
-            5 => {
-                let buf: [u8; 8] = [
-                    0, 0, payload[0], payload[1], payload[2], payload[3], payload[4], payload[5],
-                ];
-                Ok((
-                    RecordFormat::Integer48(i64::from_be_bytes(buf)),
-                    &payload[6..],
-                ))
+        }
+    }
+
+    /// Whether the value prints as `text`, checked without building the
+    /// printed string.
+    pub fn prints_as(&self, text: &str) -> bool {
+        match self {
+            RecordValue::String(s) => s == text,
+            RecordValue::Blob(b) => formats_as(format_args!("{:?}", b), text),
+            RecordValue::Scalar(RecordFormat::NULL) => text == "NULL",
+            RecordValue::Scalar(RecordFormat::Float64(f)) => {
+                formats_as(format_args!("{}", f), text)
             }
-            6 => {
-                let buf: [u8; 8] = [
-                    payload[0], payload[1], payload[2], payload[3], payload[4], payload[5],
-                    payload[6], payload[7],
-                ];
-                Ok((
-                    RecordFormat::Integer64(i64::from_be_bytes(buf)),
-                    &payload[8..],
-                ))
+            RecordValue::Scalar(value) => {
+                formats_as(format_args!("{}", value.as_i64().unwrap_or_default()), text)
             }
-            7 => {
-                let buf: [u8; 8] = [
-                    payload[0], payload[1], payload[2], payload[3], payload[4], payload[5],
-                    payload[6], payload[7],
-                ];
-                Ok((
-                    RecordFormat::Float64(f64::from_be_bytes(buf)),
-                    &payload[8..],
-                ))
+        }
+    }
+}
+
+impl<'a> From<&'a RecordFormat> for RecordValue<'a> {
+    fn from(value: &'a RecordFormat) -> Self {
+        match value {
+            RecordFormat::Blob(b) => RecordValue::Blob(b),
+            RecordFormat::String(s) => RecordValue::String(Cow::Borrowed(s)),
+            value => RecordValue::Scalar(value.clone()),
+        }
+    }
+}
+
+/// Orders two strings by their encoded bytes. Code points above U+FFFF
+/// become surrogates in UTF-16, which sort below U+E000 to U+FFFF.
+fn compare_text(a: &str, b: &str, text_encoding: TextEncoding) -> Ordering {
+    match text_encoding {
+        TextEncoding::Utf8 => a.cmp(b),
+        TextEncoding::Utf16le => a
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .cmp(b.encode_utf16().flat_map(u16::to_le_bytes)),
+        TextEncoding::Utf16be => a.encode_utf16().cmp(b.encode_utf16()),
+    }
+}
+
+/// Compares formatted output against `text` as it is written.
+fn formats_as(args: fmt::Arguments, text: &str) -> bool {
+    struct Matcher<'t>(&'t str);
+
+    impl Write for Matcher<'_> {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.0 = self.0.strip_prefix(s).ok_or(fmt::Error)?;
+            Ok(())
+        }
+    }
+
+    let mut matcher = Matcher(text);
+
+    matcher.write_fmt(args).is_ok() && matcher.0.is_empty()
+}
+
+impl RecordFormat {
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            RecordFormat::Integer8(i) => Some(*i as i64),
+            RecordFormat::Integer16(i) => Some(*i as i64),
+            RecordFormat::Integer24(i) => Some(*i as i64),
+            RecordFormat::Integer32(i) => Some(*i as i64),
+            RecordFormat::Integer48(i) => Some(*i),
+            RecordFormat::Integer64(i) => Some(*i),
+            RecordFormat::Integer0 => Some(0),
+            RecordFormat::Integer1 => Some(1),
+            _ => None,
+        }
+    }
+
+    /// Orders two stored values with the BINARY collation.
+    pub fn compare(&self, other: &RecordFormat) -> Ordering {
+        fn class(value: &RecordFormat) -> u8 {
+            match value {
+                RecordFormat::NULL => 0,
+                RecordFormat::String(_) => 2,
+                RecordFormat::Blob(_) => 3,
+                _ => 1,
             }
-            8 => Ok((RecordFormat::Integer0, payload)),
-            9 => Ok((RecordFormat::Integer1, payload)),
-            10 | 11 => bail!("Invalid record format"),
-            _ => {
-                if value % 2 == 0 {
-                    let size = (value - 12) / 2;
-                    let buf = &payload[..size as usize];
-                    Ok((RecordFormat::Blob(buf.to_vec()), &payload[size as usize..]))
-                } else {
-                    let size = (value - 13) / 2;
-                    let buf = &payload[..size as usize];
-                    let string = String::from_utf8_lossy(buf).to_string();
-                    Ok((RecordFormat::String(string), &payload[size as usize..]))
-                }
+        }
+
+        match (self, other) {
+            (RecordFormat::String(a), RecordFormat::String(b)) => a.cmp(b),
+            (RecordFormat::Blob(a), RecordFormat::Blob(b)) => a.cmp(b),
+            (RecordFormat::Float64(_), _) | (_, RecordFormat::Float64(_))
+                if class(self) == 1 && class(other) == 1 =>
+            {
+                let a = self.as_f64().unwrap_or_default();
+                let b = other.as_f64().unwrap_or_default();
+                a.partial_cmp(&b).unwrap_or(Ordering::Equal)
             }
+            (a, b) => match (a.as_i64(), b.as_i64()) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                _ => class(a).cmp(&class(b)),
+            },
         }
     }
+
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            RecordFormat::Float64(f) => Some(*f),
+            value => value.as_i64().map(|i| i as f64),
+        }
+    }
+}
+
+fn decode_text(buf: &[u8], text_encoding: TextEncoding) -> Cow<'_, str> {
+    let code_units = buf.chunks_exact(2).map(|c| [c[0], c[1]]);
+
+    match text_encoding {
+        TextEncoding::Utf8 => String::from_utf8_lossy(buf),
+        TextEncoding::Utf16le => Cow::Owned(String::from_utf16_lossy(
+            &code_units.map(u16::from_le_bytes).collect::<Vec<_>>(),
+        )),
+        TextEncoding::Utf16be => Cow::Owned(String::from_utf16_lossy(
+            &code_units.map(u16::from_be_bytes).collect::<Vec<_>>(),
+        )),
+    }
 }
 
 impl PartialEq<Token> for RecordFormat {
@@ -309,6 +459,10 @@ impl PartialEq<Token> for RecordFormat {
                 RecordFormat::Integer64(v) => *n == *v,
                 _ => false,
             },
+            Token::Float(n) => match self {
+                RecordFormat::Float64(v) => *n == *v,
+                _ => false,
+            },
             Token::Text(t) => match self {
                 RecordFormat::NULL => *t == "NULL",
                 _ => false,
@@ -320,29 +474,6 @@ impl PartialEq<Token> for RecordFormat {
             _ => false,
         }
     }
-
-    fn ne(&self, other: &Token) -> bool {
-        !self.eq(other)
-    }
-}
-
-impl From<RecordFormat> for usize {
-    fn from(record: RecordFormat) -> Self {
-        match record {
-            RecordFormat::NULL => panic!("NULL cannot be converted to usize"),
-            RecordFormat::Integer8(i) => i as usize,
-            RecordFormat::Integer16(i) => i as usize,
-            RecordFormat::Integer24(i) => i as usize,
-            RecordFormat::Integer32(i) => i as usize,
-            RecordFormat::Integer48(i) => i as usize,
-            RecordFormat::Integer64(i) => i as usize,
-            RecordFormat::Float64(_) => panic!("Float64 cannot be converted to usize"),
-            RecordFormat::Integer0 => 0,
-            RecordFormat::Integer1 => 1,
-            RecordFormat::Blob(_) => panic!("Blob cannot be converted to usize"),
-            RecordFormat::String(_) => panic!("String cannot be converted to usize"),
-        }
-    }
 }
 
 impl From<RecordFormat> for String {
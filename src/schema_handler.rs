@@ -0,0 +1,1271 @@
+use super::record_handler::RecordFormat;
+use super::tokenizer::{Token, Tokenizer};
+
+use anyhow::{anyhow, bail, Result};
+
+/// A parsed `CREATE TABLE` statement.
+#[derive(Debug, Clone)]
+pub struct TableDefinition {
+    #[allow(dead_code)]
+    pub schema: Option<String>,
+    pub name: String,
+    #[allow(dead_code)]
+    pub temporary: bool,
+    #[allow(dead_code)]
+    pub if_not_exists: bool,
+    pub columns: Vec<ColumnDefinition>,
+    pub constraints: Vec<TableConstraint>,
+    pub without_rowid: bool,
+    #[allow(dead_code)]
+    pub strict: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ColumnDefinition {
+    pub name: String,
+    /// The type as written, e.g. `DECIMAL(10,2)`.
+    pub declared_type: Option<String>,
+    #[allow(dead_code)]
+    pub affinity: Affinity,
+    pub not_null: bool,
+    pub default: Option<Vec<Token>>,
+    pub collation: Option<String>,
+    pub primary_key: Option<ColumnPrimaryKey>,
+    pub unique: bool,
+    pub checks: Vec<Vec<Token>>,
+    pub references: Option<ForeignKey>,
+    pub generated: Option<GeneratedColumn>,
+}
+
+impl ColumnDefinition {
+    /// The value of a literal DEFAULT, or NULL without one. `None` for
+    /// defaults that are expressions.
+    pub fn default_value(&self) -> Option<RecordFormat> {
+        let Some(tokens) = &self.default else {
+            return Some(RecordFormat::NULL);
+        };
+
+        let mut tokens = tokens.as_slice();
+
+        while let [Token::Punctuation('('), inner @ .., Token::Punctuation(')')] = tokens {
+            tokens = inner;
+        }
+
+        let (sign, unsigned) = match tokens {
+            [Token::Punctuation('-'), rest @ ..] => (-1, rest),
+            [Token::Punctuation('+'), rest @ ..] => (1, rest),
+            _ => (1, tokens),
+        };
+
+        match unsigned {
+            [Token::Number(n)] => Some(RecordFormat::Integer64(sign * n)),
+            // The magnitude of the smallest integer only fits once negated.
+            [Token::Float(f)] if sign == -1 && *f == -(i64::MIN as f64) => {
+                Some(RecordFormat::Integer64(i64::MIN))
+            }
+            [Token::Float(f)] => Some(RecordFormat::Float64(sign as f64 * f)),
+            _ if unsigned.len() != tokens.len() => None,
+            [Token::String(s)] => Some(RecordFormat::String(s.clone())),
+            [Token::Text(x), Token::String(hex)] if x.eq_ignore_ascii_case("X") => (0..hex.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+                .collect::<Option<Vec<_>>>()
+                .map(RecordFormat::Blob),
+            [Token::Text(t)] => match t.to_uppercase().as_str() {
+                "NULL" => Some(RecordFormat::NULL),
+                "TRUE" => Some(RecordFormat::Integer1),
+                "FALSE" => Some(RecordFormat::Integer0),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ColumnPrimaryKey {
+    pub descending: bool,
+    #[allow(dead_code)]
+    pub autoincrement: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct GeneratedColumn {
+    #[allow(dead_code)]
+    pub expression: Vec<Token>,
+    /// Stored columns are written to the record, virtual ones aren't.
+    pub stored: bool,
+}
+
+/// Type affinity derived from a declared type, by sqlite's rules.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Affinity {
+    Text,
+    Numeric,
+    Integer,
+    Real,
+    Blob,
+}
+
+impl Affinity {
+    pub fn from_declared_type(declared_type: Option<&str>) -> Self {
+        let Some(declared_type) = declared_type else {
+            return Affinity::Blob;
+        };
+
+        let declared_type = declared_type.to_uppercase();
+        let contains = |s| declared_type.contains(s);
+
+        if contains("INT") {
+            Affinity::Integer
+        } else if contains("CHAR") || contains("CLOB") || contains("TEXT") {
+            Affinity::Text
+        } else if contains("BLOB") {
+            Affinity::Blob
+        } else if contains("REAL") || contains("FLOA") || contains("DOUB") {
+            Affinity::Real
+        } else {
+            Affinity::Numeric
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TableConstraint {
+    #[allow(dead_code)]
+    pub name: Option<String>,
+    pub kind: TableConstraintKind,
+}
+
+#[derive(Debug, Clone)]
+pub enum TableConstraintKind {
+    PrimaryKey(Vec<IndexedColumn>),
+    Unique(Vec<IndexedColumn>),
+    Check(#[allow(dead_code)] Vec<Token>),
+    ForeignKey(
+        #[allow(dead_code)] Vec<String>,
+        #[allow(dead_code)] ForeignKey,
+    ),
+}
+
+#[derive(Debug, Clone)]
+pub struct ForeignKey {
+    #[allow(dead_code)]
+    pub table: String,
+    #[allow(dead_code)]
+    pub columns: Vec<String>,
+}
+
+/// An entry of a key or index column list.
+#[derive(Debug, Clone)]
+pub struct IndexedColumn {
+    pub expression: Vec<Token>,
+    pub collation: Option<String>,
+    pub descending: bool,
+}
+
+impl IndexedColumn {
+    /// The column, if the entry is a plain column rather than an expression.
+    pub fn column_name(&self) -> Option<&str> {
+        match self.expression.as_slice() {
+            [Token::Text(s) | Token::String(s)] => Some(s),
+            _ => None,
+        }
+    }
+}
+
+impl TableDefinition {
+    pub fn new(sql: &str) -> Result<Self> {
+        let mut parser = Parser::new(sql);
+
+        parser.expect("CREATE")?;
+        let temporary = parser.keyword("TEMP") || parser.keyword("TEMPORARY");
+        parser.expect("TABLE")?;
+        let if_not_exists = parser.if_not_exists()?;
+        let (schema, name) = parser.qualified_name()?;
+
+        if parser.keyword("AS") {
+            bail!("CREATE TABLE ... AS SELECT is not supported");
+        }
+
+        parser.expect_punctuation('(')?;
+
+        let mut columns = Vec::new();
+        let mut constraints = Vec::new();
+
+        loop {
+            if parser.is_table_constraint() {
+                constraints.push(parser.table_constraint()?);
+            } else if constraints.is_empty() {
+                columns.push(parser.column_definition()?);
+            } else {
+                bail!("Column definition after a table constraint");
+            }
+
+            if parser.punctuation(')') {
+                break;
+            }
+
+            parser.expect_punctuation(',')?;
+        }
+
+        let mut without_rowid = false;
+        let mut strict = false;
+
+        loop {
+            if parser.keyword("WITHOUT") {
+                parser.expect("ROWID")?;
+                without_rowid = true;
+            } else if parser.keyword("STRICT") {
+                strict = true;
+            } else {
+                break;
+            }
+
+            if !parser.punctuation(',') {
+                break;
+            }
+        }
+
+        parser.punctuation(';');
+        parser.end()?;
+
+        Ok(TableDefinition {
+            schema,
+            name,
+            temporary,
+            if_not_exists,
+            columns,
+            constraints,
+            without_rowid,
+            strict,
+        })
+    }
+
+    pub fn column(&self, name: &str) -> Option<usize> {
+        self.columns
+            .iter()
+            .position(|c| c.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Columns of the primary key, declared either on a column or as a
+    /// `PRIMARY KEY (...)` table constraint.
+    pub fn primary_key(&self) -> Result<Vec<&str>> {
+        if let Some(column) = self.columns.iter().find(|c| c.primary_key.is_some()) {
+            return Ok(vec![&column.name]);
+        }
+
+        for constraint in &self.constraints {
+            if let TableConstraintKind::PrimaryKey(columns) = &constraint.kind {
+                return columns
+                    .iter()
+                    .map(|c| {
+                        c.column_name()
+                            .ok_or_else(|| anyhow!("Expression in a PRIMARY KEY"))
+                    })
+                    .collect();
+            }
+        }
+
+        Ok(Vec::new())
+    }
+
+    /// The primary key as the index sqlite keeps it in, with the order and
+    /// collation of each column.
+    pub fn primary_key_index(&self) -> Vec<IndexedColumn> {
+        if let Some(column) = self.columns.iter().find(|c| c.primary_key.is_some()) {
+            return vec![IndexedColumn {
+                expression: vec![Token::Text(column.name.clone())],
+                collation: None,
+                descending: column.primary_key.as_ref().is_some_and(|k| k.descending),
+            }];
+        }
+
+        for constraint in &self.constraints {
+            if let TableConstraintKind::PrimaryKey(columns) = &constraint.kind {
+                return columns.clone();
+            }
+        }
+
+        Vec::new()
+    }
+
+    /// Leading columns of an index key that a seek can compare keys
+    /// against: plain columns in ascending BINARY order.
+    pub fn seek_columns(&self, key: &[IndexedColumn]) -> Vec<String> {
+        let mut columns = Vec::new();
+
+        for indexed_column in key {
+            let Some(name) = indexed_column.column_name() else {
+                break;
+            };
+            let Some(column) = self.column(name).map(|position| &self.columns[position]) else {
+                break;
+            };
+
+            let collation = indexed_column
+                .collation
+                .as_ref()
+                .or(column.collation.as_ref());
+
+            if indexed_column.descending
+                || collation.is_some_and(|c| !c.eq_ignore_ascii_case("BINARY"))
+            {
+                break;
+            }
+
+            columns.push(column.name.clone());
+        }
+
+        columns
+    }
+}
+
+/// A parsed `CREATE INDEX` statement, or the equivalent of one for an
+/// index sqlite creates itself.
+#[derive(Debug, Clone)]
+pub struct IndexDefinition {
+    #[allow(dead_code)]
+    pub schema: Option<String>,
+    pub name: String,
+    pub table: String,
+    #[allow(dead_code)]
+    pub unique: bool,
+    #[allow(dead_code)]
+    pub if_not_exists: bool,
+    pub columns: Vec<IndexedColumn>,
+    /// The predicate of a partial index.
+    pub where_clause: Option<Vec<Token>>,
+}
+
+impl IndexDefinition {
+    pub fn new(sql: &str) -> Result<Self> {
+        let mut parser = Parser::new(sql);
+
+        parser.expect("CREATE")?;
+        let unique = parser.keyword("UNIQUE");
+        parser.expect("INDEX")?;
+        let if_not_exists = parser.if_not_exists()?;
+        let (schema, name) = parser.qualified_name()?;
+        parser.expect("ON")?;
+        let table = parser.name()?;
+        let columns = parser.indexed_columns()?;
+
+        let where_clause = if parser.keyword("WHERE") {
+            let mut predicate = parser.expression(&[])?;
+
+            if predicate.last() == Some(&Token::Punctuation(';')) {
+                predicate.pop();
+            }
+
+            Some(predicate)
+        } else {
+            None
+        };
+
+        parser.punctuation(';');
+        parser.end()?;
+
+        Ok(IndexDefinition {
+            schema,
+            name,
+            table,
+            unique,
+            if_not_exists,
+            columns,
+            where_clause,
+        })
+    }
+
+    /// Leading columns of the index that a seek can compare keys against.
+    pub fn seek_columns(&self, table: &TableDefinition) -> Vec<String> {
+        table.seek_columns(&self.columns)
+    }
+}
+
+impl TableDefinition {
+    /// Position of the column aliasing the rowid: a lone primary key column
+    /// declared with the type `INTEGER`, which rows store as NULL.
+    pub fn rowid_alias(&self) -> Result<Option<usize>> {
+        if self.without_rowid {
+            return Ok(None);
+        }
+
+        let [column] = self.primary_key()?[..] else {
+            return Ok(None);
+        };
+
+        let position = self
+            .column(column)
+            .ok_or_else(|| anyhow!("Invalid primary key column {}", column))?;
+        let column = &self.columns[position];
+
+        let integer = column
+            .declared_type
+            .as_deref()
+            .is_some_and(|t| t.eq_ignore_ascii_case("INTEGER"));
+
+        // A column declared `INTEGER PRIMARY KEY DESC` is an ordinary column.
+        let descending = column.primary_key.as_ref().is_some_and(|k| k.descending);
+
+        Ok((integer && !descending).then_some(position))
+    }
+
+    pub fn column_names(&self) -> Vec<String> {
+        self.columns.iter().map(|c| c.name.clone()).collect()
+    }
+
+    /// The DEFAULT of every column, which records written before the column
+    /// was added read as. `None` for defaults that aren't a literal.
+    pub fn column_defaults(&self) -> Vec<Option<RecordFormat>> {
+        self.columns
+            .iter()
+            .map(ColumnDefinition::default_value)
+            .collect()
+    }
+
+    /// Table column stored at each position of the table's records: the
+    /// columns in table order, except that WITHOUT ROWID tables store their
+    /// primary key columns first. Virtual generated columns aren't stored.
+    pub fn record_column_order(&self) -> Result<Vec<usize>> {
+        let mut order = Vec::new();
+
+        if self.without_rowid {
+            for column in self.primary_key()? {
+                let position = self
+                    .column(column)
+                    .ok_or_else(|| anyhow!("Invalid primary key column {}", column))?;
+
+                if !order.contains(&position) {
+                    order.push(position);
+                }
+            }
+        }
+
+        for (position, column) in self.columns.iter().enumerate() {
+            let stored = column.generated.as_ref().is_none_or(|g| g.stored);
+
+            if stored && !order.contains(&position) {
+                order.push(position);
+            }
+        }
+
+        Ok(order)
+    }
+
+    /// The index sqlite creates for the `number`th PRIMARY KEY or UNIQUE
+    /// constraint needing one, named `sqlite_autoindex_<table>_<number>`.
+    pub fn autoindex(&self, name: &str, number: usize) -> Result<IndexDefinition> {
+        let rowid_alias = self.rowid_alias()?;
+        let mut keys: Vec<Vec<IndexedColumn>> = Vec::new();
+
+        let column_key = |name: &str, descending| {
+            vec![IndexedColumn {
+                expression: vec![Token::Text(name.to_string())],
+                collation: None,
+                descending,
+            }]
+        };
+
+        // Column constraints create their indexes as the columns are
+        // declared, table constraints after all of them.
+        for (position, column) in self.columns.iter().enumerate() {
+            if let Some(primary_key) = &column.primary_key {
+                if rowid_alias != Some(position) {
+                    keys.push(column_key(&column.name, primary_key.descending));
+                }
+            }
+
+            if column.unique {
+                keys.push(column_key(&column.name, false));
+            }
+        }
+
+        for constraint in &self.constraints {
+            match &constraint.kind {
+                TableConstraintKind::PrimaryKey(columns) if rowid_alias.is_none() => {
+                    keys.push(columns.clone())
+                }
+                TableConstraintKind::Unique(columns) => keys.push(columns.clone()),
+                _ => (),
+            }
+        }
+
+        let mut indexes: Vec<Vec<IndexedColumn>> = Vec::new();
+        let same_columns = |a: &[IndexedColumn], b: &[IndexedColumn]| {
+            a.len() == b.len()
+                && a.iter().zip(b).all(|(a, b)| {
+                    matches!((a.column_name(), b.column_name()), (Some(a), Some(b)) if a.eq_ignore_ascii_case(b))
+                })
+        };
+
+        // Every key takes the next number, even the primary key of a WITHOUT
+        // ROWID table, which is the table rather than an index. A key
+        // repeating the columns of an earlier one shares its index.
+        for columns in keys {
+            if !indexes.iter().any(|index| same_columns(index, &columns)) {
+                indexes.push(columns);
+            }
+        }
+
+        let columns = number
+            .checked_sub(1)
+            .and_then(|index| indexes.get(index))
+            .ok_or_else(|| anyhow!("No constraint of table {} creates {}", self.name, name))?;
+
+        Ok(IndexDefinition {
+            schema: None,
+            name: name.to_string(),
+            table: self.name.clone(),
+            unique: true,
+            if_not_exists: false,
+            columns: columns.clone(),
+            where_clause: None,
+        })
+    }
+}
+
+/// A parsed `CREATE VIEW` statement.
+#[derive(Debug, Clone)]
+pub struct ViewDefinition {
+    #[allow(dead_code)]
+    pub schema: Option<String>,
+    pub name: String,
+    #[allow(dead_code)]
+    pub temporary: bool,
+    #[allow(dead_code)]
+    pub if_not_exists: bool,
+    /// Column names given after the view's name, if any.
+    pub columns: Vec<String>,
+    /// The text of the view's SELECT, which the statement parser reads.
+    pub select: String,
+}
+
+impl ViewDefinition {
+    pub fn new(sql: &str) -> Result<Self> {
+        let mut parser = Parser::new(sql);
+
+        parser.expect("CREATE")?;
+        let temporary = parser.keyword("TEMP") || parser.keyword("TEMPORARY");
+        parser.expect("VIEW")?;
+        let if_not_exists = parser.if_not_exists()?;
+        let (schema, name) = parser.qualified_name()?;
+
+        let columns = match parser.peek() {
+            Some(Token::Punctuation('(')) => parser.names()?,
+            _ => Vec::new(),
+        };
+
+        parser.expect("AS")?;
+
+        // The SELECT is everything after `AS`, skipped token by token so
+        // that comments and quoting can't be mistaken for the keyword.
+        let mut tokenizer = Tokenizer::new(sql);
+
+        for _ in 0..parser.position {
+            tokenizer.next();
+        }
+
+        let select = tokenizer
+            .remaining()
+            .trim()
+            .trim_end_matches(';')
+            .trim_end();
+
+        if select.is_empty() {
+            bail!("Expected a SELECT, found {}", parser.found());
+        }
+
+        Ok(ViewDefinition {
+            schema,
+            name,
+            temporary,
+            if_not_exists,
+            columns,
+            select: select.to_string(),
+        })
+    }
+}
+
+/// Keywords starting a column constraint, which end the column's type.
+const COLUMN_CONSTRAINTS: [&str; 11] = [
+    "CONSTRAINT",
+    "PRIMARY",
+    "NOT",
+    "NULL",
+    "UNIQUE",
+    "CHECK",
+    "DEFAULT",
+    "COLLATE",
+    "REFERENCES",
+    "GENERATED",
+    "AS",
+];
+
+/// Recursive descent over the tokens of a statement.
+pub struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    pub fn new(sql: &str) -> Self {
+        let mut tokenizer = Tokenizer::new(sql);
+        let mut tokens = Vec::new();
+
+        while let Some(token) = tokenizer.next() {
+            tokens.push(token);
+        }
+
+        Parser {
+            tokens,
+            position: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += token.is_some() as usize;
+        token
+    }
+
+    fn is_keyword_at(&self, offset: usize, keyword: &str) -> bool {
+        matches!(
+            self.tokens.get(self.position + offset),
+            Some(Token::Text(t)) if t.eq_ignore_ascii_case(keyword)
+        )
+    }
+
+    /// Consumes `keyword` if it comes next.
+    pub fn keyword(&mut self, keyword: &str) -> bool {
+        let found = self.is_keyword_at(0, keyword);
+        self.position += found as usize;
+        found
+    }
+
+    pub fn expect(&mut self, keyword: &str) -> Result<()> {
+        if !self.keyword(keyword) {
+            bail!("Expected {}, found {}", keyword, self.found());
+        }
+
+        Ok(())
+    }
+
+    /// Consumes `c` if it comes next.
+    pub fn punctuation(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(&Token::Punctuation(c));
+        self.position += found as usize;
+        found
+    }
+
+    pub fn expect_punctuation(&mut self, c: char) -> Result<()> {
+        if !self.punctuation(c) {
+            bail!("Expected '{}', found {}", c, self.found());
+        }
+
+        Ok(())
+    }
+
+    pub fn end(&self) -> Result<()> {
+        if self.peek().is_some() {
+            bail!("Unexpected {} at the end of the statement", self.found());
+        }
+
+        Ok(())
+    }
+
+    fn found(&self) -> String {
+        match self.peek() {
+            Some(token) => format!("'{}'", token),
+            None => String::from("the end of the statement"),
+        }
+    }
+
+    pub fn name(&mut self) -> Result<String> {
+        match self.peek() {
+            Some(Token::Text(s) | Token::String(s)) => {
+                let name = s.clone();
+                self.position += 1;
+                Ok(name)
+            }
+            _ => bail!("Expected a name, found {}", self.found()),
+        }
+    }
+
+    /// `schema.name` or `name`.
+    pub fn qualified_name(&mut self) -> Result<(Option<String>, String)> {
+        let name = self.name()?;
+
+        if self.punctuation('.') {
+            return Ok((Some(name), self.name()?));
+        }
+
+        Ok((None, name))
+    }
+
+    pub fn if_not_exists(&mut self) -> Result<bool> {
+        if !self.keyword("IF") {
+            return Ok(false);
+        }
+
+        self.expect("NOT")?;
+        self.expect("EXISTS")?;
+
+        Ok(true)
+    }
+
+    /// The tokens of an expression up to the next `,` or `)` outside of
+    /// parentheses, or up to one of `stop` keywords.
+    pub fn expression(&mut self, stop: &[&str]) -> Result<Vec<Token>> {
+        let mut tokens = Vec::new();
+        let mut depth = 0;
+
+        loop {
+            match self.peek() {
+                None if depth > 0 => bail!("Unbalanced parentheses"),
+                None => break,
+                Some(Token::Punctuation(',' | ')')) if depth == 0 => break,
+                Some(Token::Text(t))
+                    if depth == 0 && stop.iter().any(|k| t.eq_ignore_ascii_case(k)) =>
+                {
+                    break
+                }
+                Some(Token::Punctuation('(')) => depth += 1,
+                Some(Token::Punctuation(')')) => depth -= 1,
+                _ => (),
+            }
+
+            tokens.extend(self.next());
+        }
+
+        if tokens.is_empty() {
+            bail!("Expected an expression, found {}", self.found());
+        }
+
+        Ok(tokens)
+    }
+
+    /// `( expression )`, returning the expression.
+    fn parenthesized(&mut self) -> Result<Vec<Token>> {
+        self.expect_punctuation('(')?;
+        let expression = self.expression(&[])?;
+        self.expect_punctuation(')')?;
+
+        Ok(expression)
+    }
+
+    /// `(column [COLLATE name] [ASC | DESC], ...)`.
+    pub fn indexed_columns(&mut self) -> Result<Vec<IndexedColumn>> {
+        self.expect_punctuation('(')?;
+
+        let mut columns = Vec::new();
+
+        loop {
+            let expression = self.expression(&["COLLATE", "ASC", "DESC"])?;
+            let collation = self.collation()?;
+            let descending = self.sort_order();
+
+            columns.push(IndexedColumn {
+                expression,
+                collation,
+                descending,
+            });
+
+            if self.punctuation(')') {
+                return Ok(columns);
+            }
+
+            self.expect_punctuation(',')?;
+        }
+    }
+
+    fn collation(&mut self) -> Result<Option<String>> {
+        if !self.keyword("COLLATE") {
+            return Ok(None);
+        }
+
+        Ok(Some(self.name()?))
+    }
+
+    /// Whether an optional `ASC` or `DESC` is `DESC`.
+    fn sort_order(&mut self) -> bool {
+        self.keyword("ASC");
+        self.keyword("DESC")
+    }
+
+    fn names(&mut self) -> Result<Vec<String>> {
+        self.expect_punctuation('(')?;
+
+        let mut names = vec![self.name()?];
+
+        while self.punctuation(',') {
+            names.push(self.name()?);
+        }
+
+        self.expect_punctuation(')')?;
+
+        Ok(names)
+    }
+
+    fn conflict_clause(&mut self) -> Result<()> {
+        if self.is_keyword_at(0, "ON") && self.is_keyword_at(1, "CONFLICT") {
+            self.position += 2;
+            self.name()?;
+        }
+
+        Ok(())
+    }
+
+    fn column_definition(&mut self) -> Result<ColumnDefinition> {
+        let name = self.name()?;
+        let declared_type = self.declared_type()?;
+
+        let mut column = ColumnDefinition {
+            name,
+            affinity: Affinity::from_declared_type(declared_type.as_deref()),
+            declared_type,
+            not_null: false,
+            default: None,
+            collation: None,
+            primary_key: None,
+            unique: false,
+            checks: Vec::new(),
+            references: None,
+            generated: None,
+        };
+
+        loop {
+            if self.keyword("CONSTRAINT") {
+                self.name()?;
+            }
+
+            if self.keyword("PRIMARY") {
+                self.expect("KEY")?;
+                let descending = self.sort_order();
+                self.conflict_clause()?;
+                let autoincrement = self.keyword("AUTOINCREMENT");
+
+                column.primary_key = Some(ColumnPrimaryKey {
+                    descending,
+                    autoincrement,
+                });
+            } else if self.keyword("NOT") {
+                self.expect("NULL")?;
+                self.conflict_clause()?;
+                column.not_null = true;
+            } else if self.keyword("NULL") {
+                self.conflict_clause()?;
+            } else if self.keyword("UNIQUE") {
+                self.conflict_clause()?;
+                column.unique = true;
+            } else if self.keyword("CHECK") {
+                column.checks.push(self.parenthesized()?);
+            } else if self.keyword("DEFAULT") {
+                column.default = Some(self.default_value()?);
+            } else if self.keyword("COLLATE") {
+                column.collation = Some(self.name()?);
+            } else if self.keyword("REFERENCES") {
+                column.references = Some(self.foreign_key()?);
+            } else if self.keyword("GENERATED") || self.is_keyword_at(0, "AS") {
+                if !self.keyword("AS") {
+                    self.expect("ALWAYS")?;
+                    self.expect("AS")?;
+                }
+
+                let expression = self.parenthesized()?;
+                let stored = self.keyword("STORED");
+                self.keyword("VIRTUAL");
+
+                column.generated = Some(GeneratedColumn { expression, stored });
+            } else {
+                break;
+            }
+        }
+
+        match self.peek() {
+            Some(Token::Punctuation(',' | ')')) => Ok(column),
+            _ => bail!(
+                "Unexpected {} in the definition of column {}",
+                self.found(),
+                column.name
+            ),
+        }
+    }
+
+    /// Type names followed by an optional `(size)` or `(precision, scale)`.
+    fn declared_type(&mut self) -> Result<Option<String>> {
+        let mut names = Vec::new();
+
+        while let Some(Token::Text(t) | Token::String(t)) = self.peek() {
+            if COLUMN_CONSTRAINTS.iter().any(|k| t.eq_ignore_ascii_case(k)) {
+                break;
+            }
+
+            names.push(t.clone());
+            self.position += 1;
+        }
+
+        if names.is_empty() {
+            return Ok(None);
+        }
+
+        let mut declared_type = names.join(" ");
+
+        if self.punctuation('(') {
+            let arguments = self.expression(&[])?;
+            let mut arguments = arguments
+                .iter()
+                .map(ToString::to_string)
+                .collect::<String>();
+
+            if self.punctuation(',') {
+                arguments.push(',');
+                arguments.extend(self.expression(&[])?.iter().map(ToString::to_string));
+            }
+
+            self.expect_punctuation(')')?;
+            declared_type.push_str(&format!("({})", arguments));
+        }
+
+        Ok(Some(declared_type))
+    }
+
+    /// A signed number, a literal, or a parenthesized expression.
+    fn default_value(&mut self) -> Result<Vec<Token>> {
+        let start = self.position;
+
+        match self.next() {
+            Some(Token::Punctuation('(')) => {
+                self.expression(&[])?;
+                self.expect_punctuation(')')?;
+            }
+            Some(Token::Punctuation('-' | '+')) => match self.next() {
+                Some(Token::Number(_) | Token::Float(_)) => (),
+                _ => bail!("Expected a number after the sign of a DEFAULT"),
+            },
+            Some(Token::Number(_) | Token::Float(_)) => (),
+            Some(Token::Text(x)) if x.eq_ignore_ascii_case("X") => {
+                if let Some(Token::String(_)) = self.peek() {
+                    self.position += 1;
+                }
+            }
+            Some(Token::Text(_) | Token::String(_)) => (),
+            _ => bail!("Invalid DEFAULT"),
+        }
+
+        Ok(self.tokens[start..self.position].to_vec())
+    }
+
+    /// `table [(column, ...)]` followed by the clauses sqlite accepts.
+    fn foreign_key(&mut self) -> Result<ForeignKey> {
+        let table = self.name()?;
+
+        let columns = match self.peek() {
+            Some(Token::Punctuation('(')) => self.names()?,
+            _ => Vec::new(),
+        };
+
+        loop {
+            if self.keyword("ON") {
+                if !self.keyword("DELETE") {
+                    self.expect("UPDATE")?;
+                }
+
+                if self.keyword("SET") {
+                    if !self.keyword("NULL") {
+                        self.expect("DEFAULT")?;
+                    }
+                } else if self.keyword("NO") {
+                    self.expect("ACTION")?;
+                } else if !self.keyword("CASCADE") {
+                    self.expect("RESTRICT")?;
+                }
+            } else if self.keyword("MATCH") {
+                self.name()?;
+            } else if self.is_keyword_at(0, "DEFERRABLE")
+                || (self.is_keyword_at(0, "NOT") && self.is_keyword_at(1, "DEFERRABLE"))
+            {
+                self.keyword("NOT");
+                self.expect("DEFERRABLE")?;
+
+                if self.keyword("INITIALLY") && !self.keyword("DEFERRED") {
+                    self.expect("IMMEDIATE")?;
+                }
+            } else {
+                return Ok(ForeignKey { table, columns });
+            }
+        }
+    }
+
+    fn is_table_constraint(&self) -> bool {
+        ["CONSTRAINT", "PRIMARY", "UNIQUE", "CHECK", "FOREIGN"]
+            .iter()
+            .any(|k| self.is_keyword_at(0, k))
+    }
+
+    fn table_constraint(&mut self) -> Result<TableConstraint> {
+        let name = if self.keyword("CONSTRAINT") {
+            Some(self.name()?)
+        } else {
+            None
+        };
+
+        let kind = if self.keyword("PRIMARY") {
+            self.expect("KEY")?;
+            let columns = self.indexed_columns()?;
+            self.conflict_clause()?;
+            TableConstraintKind::PrimaryKey(columns)
+        } else if self.keyword("UNIQUE") {
+            let columns = self.indexed_columns()?;
+            self.conflict_clause()?;
+            TableConstraintKind::Unique(columns)
+        } else if self.keyword("CHECK") {
+            TableConstraintKind::Check(self.parenthesized()?)
+        } else if self.keyword("FOREIGN") {
+            self.expect("KEY")?;
+            let columns = self.names()?;
+            self.expect("REFERENCES")?;
+            TableConstraintKind::ForeignKey(columns, self.foreign_key()?)
+        } else {
+            bail!("Expected a table constraint, found {}", self.found());
+        };
+
+        Ok(TableConstraint { name, kind })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column_names(columns: &[IndexedColumn]) -> Vec<&str> {
+        columns
+            .iter()
+            .filter_map(IndexedColumn::column_name)
+            .collect()
+    }
+
+    /// Columns of the autoindex numbered `number`, or `None` if the table
+    /// has no constraint creating it.
+    fn autoindex(sql: &str, number: usize) -> Option<Vec<String>> {
+        let table = TableDefinition::new(sql).unwrap();
+        let name = format!("sqlite_autoindex_{}_{}", table.name, number);
+        let index = table.autoindex(&name, number).ok()?;
+
+        assert!(index.unique);
+        assert_eq!(index.table, table.name);
+
+        Some(
+            column_names(&index.columns)
+                .into_iter()
+                .map(String::from)
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn table_constraints() {
+        let table = TableDefinition::new(
+            "CREATE TABLE t (a INTEGER, b TEXT, c, \
+             CONSTRAINT pk PRIMARY KEY (a, b DESC), \
+             UNIQUE (c COLLATE NOCASE), \
+             CHECK (a > 0), \
+             FOREIGN KEY (c) REFERENCES p (x))",
+        )
+        .unwrap();
+
+        assert_eq!(table.constraints.len(), 4);
+        assert_eq!(table.constraints[0].name.as_deref(), Some("pk"));
+
+        let TableConstraintKind::PrimaryKey(key) = &table.constraints[0].kind else {
+            panic!("expected a PRIMARY KEY, got {:?}", table.constraints[0]);
+        };
+        assert_eq!(column_names(key), ["a", "b"]);
+        assert!(key[1].descending);
+
+        let TableConstraintKind::Unique(key) = &table.constraints[1].kind else {
+            panic!("expected a UNIQUE, got {:?}", table.constraints[1]);
+        };
+        assert_eq!(column_names(key), ["c"]);
+        assert_eq!(key[0].collation.as_deref(), Some("NOCASE"));
+
+        assert!(matches!(
+            table.constraints[2].kind,
+            TableConstraintKind::Check(_)
+        ));
+
+        let TableConstraintKind::ForeignKey(columns, foreign_key) = &table.constraints[3].kind
+        else {
+            panic!("expected a FOREIGN KEY, got {:?}", table.constraints[3]);
+        };
+        assert_eq!(columns, &["c"]);
+        assert_eq!(foreign_key.table, "p");
+        assert_eq!(foreign_key.columns, ["x"]);
+
+        assert_eq!(table.primary_key().unwrap(), ["a", "b"]);
+        assert_eq!(table.rowid_alias().unwrap(), None);
+        assert_eq!(table.seek_columns(&table.primary_key_index()), ["a"]);
+    }
+
+    #[test]
+    fn declared_type_with_arguments() {
+        let table = TableDefinition::new(
+            "CREATE TABLE t (price DECIMAL(10,2) NOT NULL DEFAULT 0, name VARCHAR(20))",
+        )
+        .unwrap();
+
+        let price = &table.columns[0];
+        assert_eq!(price.declared_type.as_deref(), Some("DECIMAL(10,2)"));
+        assert_eq!(price.affinity, Affinity::Numeric);
+        assert!(price.not_null);
+        assert_eq!(price.default_value(), Some(RecordFormat::Integer64(0)));
+
+        let name = &table.columns[1];
+        assert_eq!(name.declared_type.as_deref(), Some("VARCHAR(20)"));
+        assert_eq!(name.affinity, Affinity::Text);
+        assert_eq!(name.default_value(), Some(RecordFormat::NULL));
+    }
+
+    #[test]
+    fn generated_columns_without_rowid() {
+        let table = TableDefinition::new(
+            "CREATE TABLE t (a TEXT, \
+             b INT GENERATED ALWAYS AS (a || 'x') VIRTUAL, \
+             c AS (length(a)) STORED, \
+             d, \
+             PRIMARY KEY (d, a)) WITHOUT ROWID",
+        )
+        .unwrap();
+
+        assert!(table.without_rowid);
+        assert!(table.columns[0].generated.is_none());
+        assert!(!table.columns[1].generated.as_ref().unwrap().stored);
+        assert!(table.columns[2].generated.as_ref().unwrap().stored);
+
+        // The key comes first and the virtual column isn't stored.
+        assert_eq!(table.record_column_order().unwrap(), [3, 0, 2]);
+        assert_eq!(table.rowid_alias().unwrap(), None);
+    }
+
+    #[test]
+    fn rowid_alias() {
+        let alias = |sql| TableDefinition::new(sql).unwrap().rowid_alias().unwrap();
+
+        assert_eq!(alias("CREATE TABLE t (x, id INTEGER PRIMARY KEY)"), Some(1));
+        assert_eq!(
+            alias("CREATE TABLE t (id INTEGER, PRIMARY KEY (id))"),
+            Some(0)
+        );
+        assert_eq!(alias("CREATE TABLE t (id INTEGER PRIMARY KEY DESC)"), None);
+        assert_eq!(alias("CREATE TABLE t (id INT PRIMARY KEY)"), None);
+        assert_eq!(
+            alias("CREATE TABLE t (id INTEGER, x, PRIMARY KEY (id, x))"),
+            None
+        );
+    }
+
+    #[test]
+    fn literal_defaults() {
+        let table = TableDefinition::new(
+            "CREATE TABLE t (a DEFAULT -9223372036854775808, b DEFAULT 9223372036854775808, \
+             c DEFAULT -1.5, d DEFAULT 'x', e DEFAULT x'0aFF', f DEFAULT TRUE, g DEFAULT (1 + 1))",
+        )
+        .unwrap();
+
+        assert_eq!(
+            table.column_defaults(),
+            [
+                Some(RecordFormat::Integer64(i64::MIN)),
+                Some(RecordFormat::Float64(9223372036854775808.0)),
+                Some(RecordFormat::Float64(-1.5)),
+                Some(RecordFormat::String("x".to_string())),
+                Some(RecordFormat::Blob(vec![0x0a, 0xff])),
+                Some(RecordFormat::Integer1),
+                None,
+            ]
+        );
+    }
+
+    // The expected names are those sqlite gives the indexes of each table.
+    #[test]
+    fn autoindex_numbering() {
+        let sql = "CREATE TABLE t (a UNIQUE, b, c PRIMARY KEY, UNIQUE (b, c), UNIQUE (a))";
+        assert_eq!(autoindex(sql, 1).unwrap(), ["a"]);
+        assert_eq!(autoindex(sql, 2).unwrap(), ["c"]);
+        assert_eq!(autoindex(sql, 3).unwrap(), ["b", "c"]);
+        assert_eq!(autoindex(sql, 4), None);
+
+        // A table constraint comes after every column constraint.
+        let sql = "CREATE TABLE u (a, b UNIQUE, PRIMARY KEY (a), UNIQUE (a))";
+        assert_eq!(autoindex(sql, 1).unwrap(), ["b"]);
+        assert_eq!(autoindex(sql, 2).unwrap(), ["a"]);
+        assert_eq!(autoindex(sql, 3), None);
+
+        // The rowid alias has no index, but a UNIQUE on it does.
+        let sql = "CREATE TABLE r (id INTEGER PRIMARY KEY, x UNIQUE, UNIQUE (id))";
+        assert_eq!(autoindex(sql, 1).unwrap(), ["x"]);
+        assert_eq!(autoindex(sql, 2).unwrap(), ["id"]);
+        assert_eq!(autoindex(sql, 3), None);
+
+        // The primary key of a WITHOUT ROWID table takes a number too.
+        let sql = "CREATE TABLE w (a, b UNIQUE, c, PRIMARY KEY (a, c), UNIQUE (c, a), \
+                   UNIQUE (a, c)) WITHOUT ROWID";
+        assert_eq!(autoindex(sql, 1).unwrap(), ["b"]);
+        assert_eq!(autoindex(sql, 3).unwrap(), ["c", "a"]);
+        assert_eq!(autoindex(sql, 4), None);
+
+        let sql = "CREATE TABLE w2 (a PRIMARY KEY, b UNIQUE, UNIQUE (a)) WITHOUT ROWID";
+        assert_eq!(autoindex(sql, 2).unwrap(), ["b"]);
+        assert_eq!(autoindex(sql, 3), None);
+    }
+
+    #[test]
+    fn create_index() {
+        let index = IndexDefinition::new(
+            "CREATE UNIQUE INDEX IF NOT EXISTS main.i ON t (a DESC, b COLLATE NOCASE, c + 1) \
+             WHERE a IS NOT NULL;",
+        )
+        .unwrap();
+
+        assert_eq!(index.schema.as_deref(), Some("main"));
+        assert_eq!(index.name, "i");
+        assert_eq!(index.table, "t");
+        assert!(index.unique);
+        assert!(index.if_not_exists);
+        assert_eq!(column_names(&index.columns), ["a", "b"]);
+        assert!(index.columns[0].descending);
+        assert_eq!(index.columns[1].collation.as_deref(), Some("NOCASE"));
+        assert_eq!(index.columns[2].column_name(), None);
+        assert_eq!(index.where_clause.unwrap().len(), 4);
+
+        let table = TableDefinition::new("CREATE TABLE t (a, b, c)").unwrap();
+        let index = IndexDefinition::new("CREATE INDEX i ON t (a, b DESC, c)").unwrap();
+        assert!(!index.unique);
+        assert_eq!(index.seek_columns(&table), ["a"]);
+    }
+
+    #[test]
+    fn create_view() {
+        let view =
+            ViewDefinition::new("CREATE TEMP VIEW IF NOT EXISTS v (x, y) AS SELECT a, b FROM t")
+                .unwrap();
+
+        assert_eq!(view.name, "v");
+        assert!(view.temporary);
+        assert!(view.if_not_exists);
+        assert_eq!(view.columns, ["x", "y"]);
+        assert_eq!(view.select, "SELECT a, b FROM t");
+    }
+
+    #[test]
+    fn invalid_statements() {
+        assert!(TableDefinition::new("CREATE TABLE t").is_err());
+        assert!(TableDefinition::new("CREATE TABLE t (a,)").is_err());
+        assert!(TableDefinition::new("CREATE TABLE t (a) garbage").is_err());
+        assert!(IndexDefinition::new("CREATE INDEX i t (a)").is_err());
+    }
+}
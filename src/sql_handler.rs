@@ -1,8 +1,73 @@
 use anyhow::{anyhow, bail, Result};
 
-use super::record_handler::SchemaRecord;
+use super::database_handler::Row;
 use super::tokenizer::{Token, Tokenizer};
-use super::Record;
+
+#[derive(Debug)]
+pub enum Statement {
+    Select(SqlStatement),
+    Pragma(PragmaStatement),
+}
+
+impl Statement {
+    pub fn new(sql: &str) -> Result<Self> {
+        match Tokenizer::new(sql).next() {
+            Some(Token::Text(t)) if t.eq_ignore_ascii_case("pragma") => {
+                Ok(Statement::Pragma(PragmaStatement::new(sql)?))
+            }
+            _ => Ok(Statement::Select(SqlStatement::new(sql)?)),
+        }
+    }
+}
+
+/// `PRAGMA name`, `PRAGMA name = value` or `PRAGMA name(value)`.
+#[derive(Debug)]
+pub struct PragmaStatement {
+    pub name: String,
+    pub value: Option<String>,
+}
+
+impl PragmaStatement {
+    fn new(sql: &str) -> Result<Self> {
+        let mut tokenizer = Tokenizer::new(sql);
+
+        tokenizer.tag("pragma")?;
+
+        let name = match tokenizer.next() {
+            Some(Token::Text(name)) => name.to_lowercase(),
+            _ => bail!("Invalid PRAGMA statement"),
+        };
+
+        let closing = match tokenizer.next() {
+            None => return Ok(PragmaStatement { name, value: None }),
+            Some(Token::Punctuation('=')) => None,
+            Some(Token::Punctuation('(')) => Some(")"),
+            _ => bail!("Invalid PRAGMA statement"),
+        };
+
+        let value = match tokenizer.next() {
+            Some(Token::Punctuation(sign @ ('-' | '+'))) => match tokenizer.next() {
+                Some(token @ (Token::Number(_) | Token::Float(_))) => format!("{}{}", sign, token),
+                _ => bail!("Invalid PRAGMA value"),
+            },
+            Some(Token::Punctuation(_)) | None => bail!("Invalid PRAGMA value"),
+            Some(token) => String::from(token),
+        };
+
+        if let Some(closing) = closing {
+            tokenizer.tag(closing)?;
+        }
+
+        if tokenizer.peek().is_some() {
+            bail!("Invalid PRAGMA statement");
+        }
+
+        Ok(PragmaStatement {
+            name,
+            value: Some(value),
+        })
+    }
+}
 
 #[derive(Debug)]
 pub struct SqlStatement {
@@ -27,7 +92,7 @@ impl SqlStatement {
                             "from" => break,
                             "count" => {
                                 tokenizer.tag("(")?;
-                                if let Some(_) = tokenizer.next() {
+                                if tokenizer.next().is_some() {
                                     result = Ok(SqlColumnVariant::Count);
                                 }
                                 tokenizer.tag(")")?;
@@ -56,7 +121,7 @@ impl SqlStatement {
 
         let mut where_clause = None;
 
-        if let Some(_) = tokenizer.peek() {
+        if tokenizer.peek().is_some() {
             where_clause = Some(SqlWhereClause::new(tokenizer.remaining())?);
         }
 
@@ -67,13 +132,30 @@ impl SqlStatement {
         })
     }
 
+    /// Names of the columns the statement selects from a table or view
+    /// with the columns `source_columns`.
+    pub fn column_names(&self, source_columns: &[String]) -> Vec<String> {
+        let mut names = Vec::new();
+
+        for c in &self.column_variants {
+            match c {
+                SqlColumnVariant::Column(column) => names.push(column.clone()),
+                SqlColumnVariant::EveryColumn => names.extend_from_slice(source_columns),
+                SqlColumnVariant::Count => names.push(String::from("count(*)")),
+            }
+        }
+
+        names
+    }
+
+    /// Resolves column names against those of the table or view; where
+    /// clause equalities on a leading prefix of `key_columns` become seek
+    /// keys.
     pub fn map(
         &self,
-        table_schema: &SchemaRecord,
-        index_schema: Option<&SchemaRecord>,
+        column_names: &[String],
+        key_columns: &[String],
     ) -> Result<MappedSqlStatement> {
-        let column_names = table_schema.table_column_names()?;
-
         let mut column_variants = Vec::new();
 
         for c in &self.column_variants {
@@ -96,7 +178,7 @@ impl SqlStatement {
         }
 
         let where_clause = if let Some(where_clause) = &self.where_clause {
-            Some(where_clause.map(table_schema, index_schema)?)
+            Some(where_clause.map(column_names, key_columns)?)
         } else {
             None
         };
@@ -117,7 +199,7 @@ pub struct SqlWhereClause {
 pub struct SqlWhereColumn {
     column: String,
     operator: SqlOperator,
-    value: String,
+    value: Token,
 }
 
 impl SqlWhereClause {
@@ -146,27 +228,53 @@ impl SqlWhereClause {
                 .next()
                 .ok_or_else(|| anyhow!("Missing value in where clause"))?;
 
-            if let Some(_) = tokenizer.peek() {
+            if tokenizer.peek().is_some() {
                 bail!("Invalid where clause");
             }
 
             columns.push(SqlWhereColumn {
                 column,
                 operator,
-                value: String::from(value),
+                value,
             });
         }
 
         Ok(SqlWhereClause { columns })
     }
 
-    fn map(
-        &self,
-        table_schema: &SchemaRecord,
-        index_schema: Option<&SchemaRecord>,
-    ) -> Result<MappedSqlWhereClause> {
-        let column_names = table_schema.table_column_names()?;
+    /// Whether every row the clause matches also satisfies `predicate`, the
+    /// WHERE of a partial index. Only terms of the predicate that restate an
+    /// equality of the clause, or require a column it equates to be NOT
+    /// NULL, are recognized.
+    pub fn implies(&self, predicate: &[Token]) -> bool {
+        let equated = |column: &str| {
+            self.columns
+                .iter()
+                .find(|c| c.column.eq_ignore_ascii_case(column))
+                .map(|c| &c.value)
+        };
+
+        conjuncts(predicate).into_iter().all(|term| match term {
+            [Token::Text(column), Token::Punctuation('='), literal]
+            | [literal, Token::Punctuation('='), Token::Text(column)] => {
+                matches!(
+                    literal,
+                    Token::Number(_) | Token::Float(_) | Token::String(_)
+                ) && equated(column) == Some(literal)
+            }
+            [Token::Text(column), is, not, null]
+                if keyword(is, "IS") && keyword(not, "NOT") && keyword(null, "NULL") =>
+            {
+                equated(column).is_some_and(|value| !keyword(value, "NULL"))
+            }
+            [Token::Text(column), notnull] if keyword(notnull, "NOTNULL") => {
+                equated(column).is_some_and(|value| !keyword(value, "NULL"))
+            }
+            _ => false,
+        })
+    }
 
+    fn map(&self, column_names: &[String], key_columns: &[String]) -> Result<MappedSqlWhereClause> {
         let mut columns = {
             let mut columns = Vec::new();
 
@@ -181,7 +289,7 @@ impl SqlWhereClause {
                 columns.push(MappedSqlWhereColumn {
                     column: position,
                     operator: column.operator,
-                    value: column.value.clone(),
+                    value: String::from(column.value.clone()),
                 });
             }
 
@@ -190,24 +298,85 @@ impl SqlWhereClause {
 
         let mut keys = Vec::new();
 
-        if let Some(index_schema) = index_schema {
-            index_schema
-                .index_columns()?
-                .iter()
-                .map(|c| column_names.iter().position(|s| s == c).unwrap())
-                .for_each(|c| {
-                    let index = columns.iter().position(|cc| cc.column == c);
+        for key_column in key_columns {
+            let Some(c) = column_names.iter().position(|s| s == key_column) else {
+                break;
+            };
 
-                    if let Some(index) = index {
-                        keys.push(columns.remove(index));
-                    }
-                });
+            match columns.iter().position(|cc| cc.column == c) {
+                Some(index) => keys.push(columns.remove(index)),
+                None => break,
+            }
         }
 
         Ok(MappedSqlWhereClause { columns, keys })
     }
 }
 
+fn keyword(token: &Token, keyword: &str) -> bool {
+    matches!(token, Token::Text(t) if t.eq_ignore_ascii_case(keyword))
+}
+
+/// Splits an expression on its top-level ANDs, looking inside terms that
+/// are wrapped in parentheses.
+fn conjuncts(expression: &[Token]) -> Vec<&[Token]> {
+    let mut pending = vec![expression];
+    let mut terms = Vec::new();
+
+    while let Some(expression) = pending.pop() {
+        if let [Token::Punctuation('('), inner @ .., Token::Punctuation(')')] = expression {
+            if closes_at_end(expression) {
+                pending.push(inner);
+                continue;
+            }
+        }
+
+        let mut parts = Vec::new();
+        let mut depth = 0;
+        let mut start = 0;
+
+        for (i, token) in expression.iter().enumerate() {
+            match token {
+                Token::Punctuation('(') => depth += 1,
+                Token::Punctuation(')') => depth -= 1,
+                token if depth == 0 && keyword(token, "AND") => {
+                    parts.push(&expression[start..i]);
+                    start = i + 1;
+                }
+                _ => (),
+            }
+        }
+
+        if parts.is_empty() {
+            terms.push(expression);
+        } else {
+            pending.extend(parts);
+            pending.push(&expression[start..]);
+        }
+    }
+
+    terms
+}
+
+/// Whether the parenthesis opening `tokens` is closed by its last token.
+fn closes_at_end(tokens: &[Token]) -> bool {
+    let mut depth = 0;
+
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Punctuation('(') => depth += 1,
+            Token::Punctuation(')') => depth -= 1,
+            _ => (),
+        }
+
+        if depth == 0 {
+            return i == tokens.len() - 1;
+        }
+    }
+
+    false
+}
+
 #[derive(Debug, Copy, Clone)]
 enum SqlOperator {
     Equal,
@@ -227,31 +396,26 @@ pub struct MappedSqlWhereClause {
 }
 
 impl MappedSqlWhereClause {
-    pub fn matches(&self, record: &Record) -> bool {
+    /// Checks a row against the where clause, decoding only the columns
+    /// the clause compares and without copying them out of the row.
+    pub fn matches(&self, row: &Row) -> Result<bool> {
         for c in &self.columns {
-            let column_value = &record.values[c.column];
-            let value = &c.value;
+            let column_value = row.value(c.column)?;
 
             match c.operator {
                 SqlOperator::Equal => {
-                    if String::from(column_value) != *value {
-                        return false;
+                    if !column_value.prints_as(&c.value) {
+                        return Ok(false);
                     }
                 }
             }
         }
 
-        true
+        Ok(true)
     }
 
     pub fn keys(&self) -> Vec<String> {
-        let mut keys = Vec::new();
-
-        for c in &self.keys {
-            keys.push(c.value.clone());
-        }
-
-        keys.iter().map(|s| s.clone()).collect()
+        self.keys.iter().map(|c| c.value.clone()).collect()
     }
 }
 
@@ -274,3 +438,51 @@ pub enum MappedSqlColumnVariant {
     Column(usize),
     EveryColumn,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::schema_handler::IndexDefinition;
+    use super::*;
+
+    /// Whether the WHERE of `select` implies the WHERE of a partial index.
+    fn implies(select: &str, predicate: &str) -> bool {
+        let sql = format!("CREATE INDEX i ON t (a) WHERE {}", predicate);
+        let predicate = IndexDefinition::new(&sql).unwrap().where_clause.unwrap();
+        let statement = SqlStatement::new(select).unwrap();
+
+        statement.where_clause.unwrap().implies(&predicate)
+    }
+
+    #[test]
+    fn implies_restated_equalities() {
+        assert!(implies("select * from t where a = 1", "a = 1"));
+        assert!(implies("select * from t where a = 1", "1 = a"));
+        assert!(implies("select * from t where A = 1", "a = 1"));
+        assert!(implies("select * from t where a = 'x'", "a = 'x'"));
+        assert!(implies("select * from t where a = 1.5", "(a = 1.5)"));
+    }
+
+    #[test]
+    fn implies_not_null() {
+        assert!(implies("select * from t where a = 1", "a IS NOT NULL"));
+        assert!(implies("select * from t where a = 'x'", "a NOTNULL"));
+        assert!(implies(
+            "select * from t where a = 1",
+            "a = 1 AND (a IS NOT NULL AND 1 = a)"
+        ));
+    }
+
+    #[test]
+    fn does_not_imply() {
+        assert!(!implies("select * from t where a = 1", "a = 2"));
+        assert!(!implies("select * from t where a = 1", "a = '1'"));
+        assert!(!implies("select * from t where a = 1", "b = 1"));
+        assert!(!implies("select * from t where a = 1", "b IS NOT NULL"));
+        assert!(!implies("select * from t where a = null", "a IS NOT NULL"));
+        assert!(!implies("select * from t where a = b", "a = b"));
+        assert!(!implies("select * from t where a = 1", "a > 0"));
+        assert!(!implies("select * from t where a = 1", "a = 1 OR b = 1"));
+        assert!(!implies("select * from t where a = 1", "a = 1 AND b = 1"));
+        assert!(!implies("select * from t where a = 1", "a IS NULL"));
+    }
+}
@@ -1,39 +1,79 @@
 use anyhow::{bail, Result};
 use std::fmt;
 
+/// Splits SQL text into tokens. Indexes count chars, not bytes.
 pub struct Tokenizer<'a> {
     input: &'a str,
+    chars: Vec<char>,
     index: usize,
 }
 
 impl Tokenizer<'_> {
-    pub fn new<'a>(input: &'a str) -> Tokenizer {
-        Tokenizer { input, index: 0 }
+    pub fn new(input: &str) -> Tokenizer<'_> {
+        Tokenizer {
+            input,
+            chars: input.chars().collect(),
+            index: 0,
+        }
     }
 
     fn get_char(&self, index: usize) -> (Option<char>, usize) {
-        let c = self.input.chars().nth(index);
+        let c = self.chars.get(index).copied();
         (c, index + 1)
     }
 
-    pub fn take_while(&mut self, func: impl Fn(&Token) -> bool) -> Vec<Token> {
-        let mut result = Vec::new();
-        let mut index = self.index;
-
-        while let (Some(t), i) = self.get_token(index) {
-            if !func(&t) {
+    fn skip_digits(&self, mut index: usize) -> usize {
+        while let (Some(c), i) = self.get_char(index) {
+            if !c.is_ascii_digit() {
                 break;
             }
 
             index = i;
-            result.push(t);
         }
 
-        self.index = index;
-        result
+        index
+    }
+
+    fn slice(&self, start: usize, end: usize) -> String {
+        self.chars[start..end].iter().collect()
+    }
+
+    /// Skips whitespace and comments; an unterminated comment runs to the
+    /// end of the input.
+    fn skip_blanks(&self, mut index: usize) -> usize {
+        loop {
+            match self.get_char(index) {
+                (Some(c), i) if c.is_whitespace() => index = i,
+                (Some('-'), i) if self.get_char(i).0 == Some('-') => {
+                    index = i;
+
+                    while let (Some(c), i) = self.get_char(index) {
+                        index = i;
+
+                        if c == '\n' {
+                            break;
+                        }
+                    }
+                }
+                (Some('/'), i) if self.get_char(i).0 == Some('*') => {
+                    index = i + 1;
+
+                    while let (Some(c), i) = self.get_char(index) {
+                        index = i;
+
+                        if c == '*' && self.get_char(i).0 == Some('/') {
+                            index += 1;
+                            break;
+                        }
+                    }
+                }
+                _ => return index,
+            }
+        }
     }
 
     fn get_token(&self, index: usize) -> (Option<Token>, usize) {
+        let index = self.skip_blanks(index);
         let (c, i) = self.get_char(index);
 
         if c.is_none() {
@@ -43,17 +83,36 @@ impl Tokenizer<'_> {
         let mut index_iter = i;
         let c = c.unwrap();
         match c {
-            c if c.is_numeric() => {
-                while let (Some(c), i) = self.get_char(index_iter) {
-                    if !c.is_numeric() {
-                        break;
-                    }
+            c if c.is_ascii_digit() => {
+                index_iter = self.skip_digits(index_iter);
+                let mut float = false;
 
-                    index_iter = i;
+                if let (Some('.'), i) = self.get_char(index_iter) {
+                    index_iter = self.skip_digits(i);
+                    float = true;
                 }
-                let result = &self.input[index..index_iter];
-                let number = result.parse::<i64>().unwrap();
-                (Some(Token::Number(number)), index_iter)
+
+                if let (Some('e' | 'E'), i) = self.get_char(index_iter) {
+                    let i = match self.get_char(i) {
+                        (Some('+' | '-'), i) => i,
+                        _ => i,
+                    };
+
+                    if matches!(self.get_char(i), (Some(c), _) if c.is_ascii_digit()) {
+                        index_iter = self.skip_digits(i);
+                        float = true;
+                    }
+                }
+
+                // Integers too large for 64 bits are read as floats, as sqlite does.
+                let result = self.slice(index, index_iter);
+                let token = match (float, result.parse::<i64>(), result.parse::<f64>()) {
+                    (false, Ok(number), _) => Token::Number(number),
+                    (_, _, Ok(number)) => Token::Float(number),
+                    _ => Token::Text(result),
+                };
+
+                (Some(token), index_iter)
             }
             c if c.is_alphabetic() => {
                 while let (Some(c), i) = self.get_char(index_iter) {
@@ -63,46 +122,54 @@ impl Tokenizer<'_> {
 
                     index_iter = i;
                 }
-                let result = &self.input[index..index_iter];
-                (Some(Token::Text(String::from(result))), index_iter)
+                (Some(Token::Text(self.slice(index, index_iter))), index_iter)
             }
-            c if c == '\'' || c == '"' => {
-                while let (Some(ch), i) = self.get_char(index_iter) {
-                    if ch == c {
-                        if let (Some(ch), _) = self.get_char(i) {
-                            if ch == c {
-                                index_iter = i + 1;
-                                continue;
-                            }
-                        }
+            '[' => {
+                while let (Some(c), i) = self.get_char(index_iter) {
+                    index_iter = i;
 
-                        index_iter = i;
-                        break;
+                    if c == ']' {
+                        return (Some(Token::String(self.slice(index + 1, i - 1))), i);
                     }
-
-                    index_iter = i;
                 }
 
-                let result = &self.input[index + 1..index_iter - 1];
-                return (Some(Token::String(String::from(result))), index_iter);
+                (
+                    Some(Token::String(self.slice(index + 1, index_iter))),
+                    index_iter,
+                )
             }
-            ch if " \n\t".contains(c) => {
-                while let (Some(c), i) = self.get_char(index_iter) {
-                    if c != ch {
-                        break;
-                    }
+            c if c == '\'' || c == '"' || c == '`' => {
+                let mut result = String::new();
 
+                // A doubled quote is an escaped quote; an unterminated
+                // string runs to the end of the input.
+                while let (Some(ch), i) = self.get_char(index_iter) {
                     index_iter = i;
+
+                    if ch == c {
+                        match self.get_char(i) {
+                            (Some(next), i) if next == c => index_iter = i,
+                            _ => break,
+                        }
+                    }
+
+                    result.push(ch);
                 }
 
-                return self.get_token(index_iter);
+                (Some(Token::String(result)), index_iter)
             }
             _ => (Some(Token::Punctuation(c)), index_iter),
         }
     }
 
     pub fn remaining(&self) -> &str {
-        &self.input[self.index..]
+        let offset = self
+            .input
+            .char_indices()
+            .nth(self.index)
+            .map_or(self.input.len(), |(offset, _)| offset);
+
+        &self.input[offset..]
     }
 
     pub fn peek(&mut self) -> Option<Token> {
@@ -148,9 +215,10 @@ impl Tokenizer<'_> {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Token {
     Number(i64),
+    Float(f64),
     String(String),
     Text(String),
     Punctuation(char),
@@ -160,6 +228,7 @@ impl From<Token> for String {
     fn from(t: Token) -> Self {
         match t {
             Token::Number(n) => n.to_string(),
+            Token::Float(n) => n.to_string(),
             Token::String(s) => s,
             Token::Text(t) => t,
             Token::Punctuation(c) => String::from(c),
@@ -171,6 +240,7 @@ impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Token::Number(n) => write!(f, "{}", n),
+            Token::Float(n) => write!(f, "{}", n),
             Token::String(s) => write!(f, "{}", s),
             Token::Text(t) => write!(f, "{}", t),
             Token::Punctuation(c) => write!(f, "{}", c),
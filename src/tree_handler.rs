@@ -1,260 +1,913 @@
-use super::record_handler::{RecordFormat, Varint};
-use super::CellIterator;
+use super::cache_handler::{PageCache, PageCacheStats, DEFAULT_CACHE_SIZE};
+use super::error_handler::{DecodeError, DecodeErrorKind};
+use super::header_handler::{DatabaseHeader, TextEncoding};
+use super::record_handler::{RecordFormat, RecordValue, Varint};
+use super::vfs_handler::{self, PageAccess, PageBytes, PageSource};
+use super::wal_handler::Wal;
 
 use anyhow::{bail, Result};
-use std::fs::File;
-use std::io::{Read, Seek};
+use std::borrow::Cow;
+use std::rc::Rc;
 
 #[derive(Debug)]
 pub struct PageReader {
-    file: File,
-    page_size: u16,
+    source: Box<dyn PageSource>,
+    header: DatabaseHeader,
+    wal: Option<Wal>,
+    page_count: u32,
+    file_len: u64,
+    cache: PageCache,
+    cache_size: i64,
 }
 
 impl PageReader {
-    pub fn new(file_name: &str) -> Result<Self> {
-        let mut file = File::open(file_name)?;
-        let mut header = [0; 100];
-        file.read_exact(&mut header)?;
+    pub fn new(file_name: &str, access: PageAccess) -> Result<Self> {
+        let mut page_reader = Self::from_source(vfs_handler::open(file_name, access)?)?;
 
-        let page_size = u16::from_be_bytes([header[16], header[17]]);
-        file.seek(std::io::SeekFrom::Start(0))?;
+        // Read and write versions of 2 mark a database in WAL mode.
+        if page_reader.header.read_version == 2 {
+            page_reader.wal = Wal::open(file_name, page_reader.header.page_size)?;
+        }
+
+        if let Some(page_count) = page_reader.wal.as_ref().and_then(|wal| wal.page_count()) {
+            // The log may hold a newer first page, and with it a newer header.
+            let page = page_reader.read_raw_page(1)?;
+            page_reader.header = DatabaseHeader::new(&page)?;
+            page_reader.page_count = page_count;
+        }
+
+        Ok(page_reader)
+    }
 
-        Ok(Self { file, page_size })
+    /// Reads a database from any byte source. Only file databases have a
+    /// write-ahead log next to them, so none is looked for.
+    pub fn from_source(mut source: Box<dyn PageSource>) -> Result<Self> {
+        let header = DatabaseHeader::new(&source.read(0, DatabaseHeader::SIZE)?)?;
+
+        let file_len = source.len()?;
+        let page_count = header
+            .valid_page_count()
+            .unwrap_or((file_len / header.page_size as u64) as u32);
+
+        let cache = PageCache::new(PageCache::capacity_for(
+            DEFAULT_CACHE_SIZE,
+            header.page_size,
+        ));
+
+        Ok(Self {
+            source,
+            header,
+            wal: None,
+            page_count,
+            file_len,
+            cache,
+            cache_size: DEFAULT_CACHE_SIZE,
+        })
     }
 
-    pub fn read_page(&mut self, page_number: u32) -> Result<BTreePage> {
-        let mut page = vec![0; self.page_size as usize];
-        self.file.seek(std::io::SeekFrom::Start(
-            ((page_number - 1) * self.page_size as u32) as u64,
-        ))?;
-        self.file.read_exact(&mut page)?;
+    pub fn read_page(&mut self, page_number: u32) -> Result<Rc<BTreePage>> {
+        if let Some(page) = self.cache.get(page_number) {
+            return Ok(page);
+        }
+
+        let header = self.header;
+        let page = self.read_raw_page(page_number)?;
 
-        if page_number == 1 {
-            Ok(BTreePage::with_offset_header(page, 100)?)
+        let b_tree_page = Rc::new(if page_number == 1 {
+            BTreePage::with_offset_header(page_number, page, DatabaseHeader::SIZE, &header)?
         } else {
-            Ok(BTreePage::new(page)?)
+            BTreePage::new(page_number, page, &header)?
+        });
+        self.cache.insert(page_number, b_tree_page.clone());
+
+        Ok(b_tree_page)
+    }
+
+    /// Reads a page from the WAL or the main file, sharing it instead of
+    /// copying when the source keeps the whole file in memory.
+    pub fn read_raw_page(&mut self, page_number: u32) -> Result<PageBytes> {
+        if page_number == 0 || page_number > self.page_count {
+            bail!(
+                "Page {} is out of range, the database has {} pages",
+                page_number,
+                self.page_count
+            );
+        }
+
+        if let Some(wal) = &mut self.wal {
+            if let Some(page) = wal.read_page(page_number)? {
+                return Ok(PageBytes::Owned(page));
+            }
+        }
+
+        let page_size = self.header.page_size as u64;
+        let offset = (page_number as u64 - 1) * page_size;
+
+        if offset + page_size > self.file_len {
+            bail!(
+                "Page {} is past the end of the file ({} bytes)",
+                page_number,
+                self.file_len
+            );
+        }
+
+        self.source.read(offset, page_size as usize)
+    }
+
+    /// Appends the overflow chain of a cell to its locally stored payload,
+    /// which then stops borrowing from the page.
+    pub fn read_overflow(&mut self, cell: &mut BTreeCell<'_>) -> Result<()> {
+        let (payload, payload_size, first_overflow_page, page_number, payload_offset) = match cell {
+            BTreeCell::InteriorIndexCell(cell) => (
+                &mut cell.payload,
+                &cell.payload_size,
+                cell.first_overflow_page,
+                cell.page_number,
+                cell.payload_offset,
+            ),
+            BTreeCell::LeafIndexCell(cell) => (
+                &mut cell.payload,
+                &cell.payload_size,
+                cell.first_overflow_page,
+                cell.page_number,
+                cell.payload_offset,
+            ),
+            BTreeCell::LeafTableCell(cell) => (
+                &mut cell.payload,
+                &cell.payload_size,
+                cell.first_overflow_page,
+                cell.page_number,
+                cell.payload_offset,
+            ),
+            BTreeCell::InteriorTableCell(_) => return Ok(()),
+        };
+
+        let payload_size = payload_size.value as usize;
+        let mut next_page = first_overflow_page;
+        let mut overflow_pages = 0;
+
+        while payload.len() < payload_size {
+            let overflow_page = match next_page {
+                Some(overflow_page) if overflow_page != 0 => overflow_page,
+                _ => {
+                    return Err(DecodeErrorKind::OverflowChainTooShort
+                        .at(page_number, payload_offset)
+                        .into())
+                }
+            };
+
+            // A chain visiting more pages than the file holds must be a cycle.
+            overflow_pages += 1;
+            if overflow_pages > self.page_count {
+                return Err(DecodeErrorKind::OverflowChainTooLong
+                    .at(page_number, payload_offset)
+                    .into());
+            }
+
+            let usable_size = self.usable_size();
+            let page = self.read_raw_page(overflow_page)?;
+            next_page = Some(u32::from_be_bytes(page[..4].try_into()?));
+
+            let content = &page[4..usable_size];
+            let remaining = (payload_size - payload.len()).min(content.len());
+            payload.to_mut().extend_from_slice(&content[..remaining]);
         }
+
+        Ok(())
+    }
+
+    pub fn page_count(&self) -> u32 {
+        self.page_count
+    }
+
+    pub fn header(&self) -> &DatabaseHeader {
+        &self.header
+    }
+
+    /// Sets the page cache size, in pages or in KiB when negative.
+    pub fn set_cache_size(&mut self, cache_size: i64) {
+        self.cache_size = cache_size;
+        self.cache
+            .set_capacity(PageCache::capacity_for(cache_size, self.header.page_size));
+    }
+
+    pub fn cache_size(&self) -> i64 {
+        self.cache_size
+    }
+
+    pub fn cache_stats(&self) -> PageCacheStats {
+        self.cache.stats
     }
 
-    pub fn page_size(&self) -> u16 {
-        self.page_size
+    /// Page size minus the bytes reserved at the end of every page.
+    pub fn usable_size(&self) -> usize {
+        self.header.usable_size() as usize
     }
 }
 
-#[derive(Debug)]
-pub enum BTreeCell {
-    InteriorIndexCell(BTreeInteriorIndexCell),
+#[derive(Debug, Clone)]
+#[allow(clippy::enum_variant_names)]
+pub enum BTreeCell<'a> {
+    InteriorIndexCell(BTreeInteriorIndexCell<'a>),
     InteriorTableCell(BTreeInteriorTableCell),
-    LeafIndexCell(BTreeLeafIndexCell),
-    LeafTableCell(BTreeLeafTableCell),
+    LeafIndexCell(BTreeLeafIndexCell<'a>),
+    LeafTableCell(BTreeLeafTableCell<'a>),
 }
 
-#[derive(Debug)]
-pub struct BTreeInteriorIndexCell {
+impl BTreeCell<'_> {
+    /// Total payload size, including the part stored in overflow pages.
+    pub fn payload_size(&self) -> Option<usize> {
+        match self {
+            BTreeCell::InteriorIndexCell(cell) => Some(cell.payload_size.value as usize),
+            BTreeCell::InteriorTableCell(_) => None,
+            BTreeCell::LeafIndexCell(cell) => Some(cell.payload_size.value as usize),
+            BTreeCell::LeafTableCell(cell) => Some(cell.payload_size.value as usize),
+        }
+    }
+
+    pub fn first_overflow_page(&self) -> Option<u32> {
+        match self {
+            BTreeCell::InteriorIndexCell(cell) => cell.first_overflow_page,
+            BTreeCell::InteriorTableCell(_) => None,
+            BTreeCell::LeafIndexCell(cell) => cell.first_overflow_page,
+            BTreeCell::LeafTableCell(cell) => cell.first_overflow_page,
+        }
+    }
+
+    /// The record of every cell but an interior table cell: its payload,
+    /// text encoding and where the payload sits.
+    fn record(&self) -> Option<(&[u8], TextEncoding, u32, usize)> {
+        match self {
+            BTreeCell::InteriorIndexCell(cell) => Some((
+                &cell.payload,
+                cell.text_encoding,
+                cell.page_number,
+                cell.payload_offset,
+            )),
+            BTreeCell::InteriorTableCell(_) => None,
+            BTreeCell::LeafIndexCell(cell) => Some((
+                &cell.payload,
+                cell.text_encoding,
+                cell.page_number,
+                cell.payload_offset,
+            )),
+            BTreeCell::LeafTableCell(cell) => Some((
+                &cell.payload,
+                cell.text_encoding,
+                cell.page_number,
+                cell.payload_offset,
+            )),
+        }
+    }
+
+    pub fn values(&self) -> Option<Result<Vec<RecordFormat>, DecodeError>> {
+        let (payload, text_encoding, page_number, payload_offset) = self.record()?;
+
+        Some(values(payload, text_encoding, page_number, payload_offset))
+    }
+
+    /// The payload as far as it has been read: the part on the page, unless
+    /// the overflow chain has been read too.
+    pub fn payload(&self) -> Option<&[u8]> {
+        self.record().map(|(payload, ..)| payload)
+    }
+
+    pub fn record_header(&self) -> Option<Result<RecordHeader, DecodeError>> {
+        let (payload, text_encoding, page_number, payload_offset) = self.record()?;
+
+        Some(RecordHeader::new(
+            payload,
+            self.payload_size()?,
+            text_encoding,
+            page_number,
+            payload_offset,
+        ))
+    }
+
+    /// Borrows one column of the cell's record, see `RecordHeader::value`.
+    pub fn value(
+        &self,
+        header: &RecordHeader,
+        index: usize,
+    ) -> Option<Result<RecordValue<'_>, DecodeError>> {
+        header.value(self.record()?.0, index)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BTreeInteriorIndexCell<'a> {
     pub left_child_page: u32,
-    //payload_size: Varint,
-    payload: Vec<u8>,
-    //first_overflow_page: u32,
+    payload_size: Varint,
+    payload: Cow<'a, [u8]>,
+    first_overflow_page: Option<u32>,
+    text_encoding: TextEncoding,
+    page_number: u32,
+    payload_offset: usize,
 }
 
-impl BTreeInteriorIndexCell {
-    pub fn values(&self) -> Result<Vec<RecordFormat>> {
-        values(&self.payload)
+impl BTreeInteriorIndexCell<'_> {
+    pub fn values(&self) -> Result<Vec<RecordFormat>, DecodeError> {
+        values(
+            &self.payload,
+            self.text_encoding,
+            self.page_number,
+            self.payload_offset,
+        )
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BTreeInteriorTableCell {
     pub left_child_page: u32,
     pub row_id: Varint,
 }
 
-#[derive(Debug)]
-pub struct BTreeLeafTableCell {
-    //payload_size: Varint,
+#[derive(Debug, Clone)]
+pub struct BTreeLeafTableCell<'a> {
+    payload_size: Varint,
     pub row_id: Varint,
-    payload: Vec<u8>,
-    //first_overflow_page: u32,
+    payload: Cow<'a, [u8]>,
+    first_overflow_page: Option<u32>,
+    text_encoding: TextEncoding,
+    page_number: u32,
+    payload_offset: usize,
+}
+
+/// Decodes a record. Errors are located on `page_number`, counting from
+/// `payload_offset`, where the payload starts on that page.
+pub fn values(
+    payload: &[u8],
+    text_encoding: TextEncoding,
+    page_number: u32,
+    payload_offset: usize,
+) -> Result<Vec<RecordFormat>, DecodeError> {
+    let header = RecordHeader::new(
+        payload,
+        payload.len(),
+        text_encoding,
+        page_number,
+        payload_offset,
+    )?;
+
+    (0..header.column_count())
+        .filter_map(|index| header.column(payload, index))
+        .collect()
+}
+
+/// The serial types of a record, parsed once so that single columns can be
+/// decoded without touching the others.
+#[derive(Debug, Clone)]
+pub struct RecordHeader {
+    /// Serial type and payload offset of every column.
+    columns: Vec<(i64, usize)>,
+    text_encoding: TextEncoding,
+    page_number: u32,
+    payload_offset: usize,
 }
 
-pub fn values(payload: &Vec<u8>) -> Result<Vec<RecordFormat>> {
-    let mut header = Vec::new();
-    let mut values = Vec::new();
-    let mut payload = payload.as_slice();
+impl RecordHeader {
+    /// Parses the header of `payload`, the first bytes of a record of
+    /// `payload_size` bytes, checking that the body holds every column it
+    /// declares. The header itself has to be within `payload`.
+    pub fn new(
+        payload: &[u8],
+        payload_size: usize,
+        text_encoding: TextEncoding,
+        page_number: u32,
+        payload_offset: usize,
+    ) -> Result<Self, DecodeError> {
+        let error = |offset: usize, kind: DecodeErrorKind| -> DecodeError {
+            kind.at(page_number, payload_offset + offset)
+        };
+
+        let (header_size, _) = Varint::from(payload).map_err(|kind| error(0, kind))?;
+
+        if header_size.value < header_size.size as i64 || header_size.value as usize > payload.len()
+        {
+            return Err(error(
+                0,
+                DecodeErrorKind::InvalidRecordHeader(header_size.value),
+            ));
+        }
+
+        let mut header_bytes = &payload[header_size.size as usize..header_size.value as usize];
+        let mut offset = header_size.value as usize;
+        let mut columns = Vec::new();
+
+        while !header_bytes.is_empty() {
+            let header_offset = payload.len() - header_bytes.len();
+            let (serial_type, rest) =
+                Varint::from(header_bytes).map_err(|kind| error(header_offset, kind))?;
+            header_bytes = rest;
 
-    let (header_size_varint, remaining) = Varint::from(payload);
-    payload = remaining;
-    let mut header_size = header_size_varint.value - header_size_varint.size as i64;
+            let size =
+                RecordFormat::size(serial_type.value).map_err(|kind| error(header_offset, kind))?;
+            if offset + size > payload_size {
+                return Err(error(payload_size, DecodeErrorKind::UnexpectedEnd));
+            }
 
-    while header_size > 0 {
-        let (varint, remaining) = Varint::from(payload);
-        payload = remaining;
-        header_size -= varint.size as i64;
-        header.push(varint);
+            columns.push((serial_type.value, offset));
+            offset += size;
+        }
+
+        Ok(RecordHeader {
+            columns,
+            text_encoding,
+            page_number,
+            payload_offset,
+        })
+    }
+
+    pub fn column_count(&self) -> usize {
+        self.columns.len()
     }
 
-    for v in &header {
-        let (record_format, remaining) = RecordFormat::new(payload, v.value)?;
-        payload = remaining;
-        values.push(record_format);
+    /// Whether column `index` ends within the first `len` bytes of the
+    /// payload, such as the part of it stored on the page.
+    pub fn is_within(&self, index: usize, len: usize) -> bool {
+        match self.columns.get(index) {
+            Some((serial_type, offset)) => {
+                RecordFormat::size(*serial_type).is_ok_and(|size| offset + size <= len)
+            }
+            None => true,
+        }
+    }
+
+    /// Decodes a single column of the record `payload` this header was
+    /// parsed from.
+    pub fn column(
+        &self,
+        payload: &[u8],
+        index: usize,
+    ) -> Option<Result<RecordFormat, DecodeError>> {
+        self.value(payload, index)
+            .map(|value| value.map(RecordValue::into_owned))
     }
 
-    Ok(values)
+    /// Decodes a single column, borrowing text and blobs from `payload`.
+    pub fn value<'p>(
+        &self,
+        payload: &'p [u8],
+        index: usize,
+    ) -> Option<Result<RecordValue<'p>, DecodeError>> {
+        let (serial_type, offset) = *self.columns.get(index)?;
+
+        Some(
+            payload
+                .get(offset..)
+                .ok_or(DecodeErrorKind::UnexpectedEnd)
+                .and_then(|body| RecordValue::new(body, serial_type, self.text_encoding))
+                .map(|(value, _)| value)
+                .map_err(|kind| kind.at(self.page_number, self.payload_offset + offset)),
+        )
+    }
 }
 
-impl BTreeLeafTableCell {
-    pub fn values(&self) -> Result<Vec<RecordFormat>> {
-        values(&self.payload)
+impl BTreeLeafTableCell<'_> {
+    pub fn values(&self) -> Result<Vec<RecordFormat>, DecodeError> {
+        values(
+            &self.payload,
+            self.text_encoding,
+            self.page_number,
+            self.payload_offset,
+        )
+    }
+
+    pub fn record_header(&self) -> Result<RecordHeader, DecodeError> {
+        RecordHeader::new(
+            &self.payload,
+            self.payload_size.value as usize,
+            self.text_encoding,
+            self.page_number,
+            self.payload_offset,
+        )
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    pub fn first_overflow_page(&self) -> Option<u32> {
+        self.first_overflow_page
+    }
+
+    /// Copies the payload out of the page, so the cell can outlive it.
+    pub fn into_owned(self) -> BTreeLeafTableCell<'static> {
+        BTreeLeafTableCell {
+            payload_size: self.payload_size,
+            row_id: self.row_id,
+            payload: Cow::Owned(self.payload.into_owned()),
+            first_overflow_page: self.first_overflow_page,
+            text_encoding: self.text_encoding,
+            page_number: self.page_number,
+            payload_offset: self.payload_offset,
+        }
+    }
+
+    /// Locates an error in the payload of this cell.
+    pub fn error(&self, kind: DecodeErrorKind) -> DecodeError {
+        kind.at(self.page_number, self.payload_offset)
     }
 }
 
-#[derive(Debug)]
-pub struct BTreeLeafIndexCell {
-    //payload_size: Varint,
-    payload: Vec<u8>,
-    //first_overflow_page: u32,
+#[derive(Debug, Clone)]
+pub struct BTreeLeafIndexCell<'a> {
+    payload_size: Varint,
+    payload: Cow<'a, [u8]>,
+    first_overflow_page: Option<u32>,
+    text_encoding: TextEncoding,
+    page_number: u32,
+    payload_offset: usize,
 }
 
-impl BTreeLeafIndexCell {
-    pub fn values(&self) -> Result<Vec<RecordFormat>> {
-        values(&self.payload)
+impl BTreeLeafIndexCell<'_> {
+    pub fn values(&self) -> Result<Vec<RecordFormat>, DecodeError> {
+        values(
+            &self.payload,
+            self.text_encoding,
+            self.page_number,
+            self.payload_offset,
+        )
     }
 }
 
+/// A b-tree page whose cells are decoded on demand from the page buffer.
 #[derive(Debug)]
 pub struct BTreePage {
+    pub page_number: u32,
     pub page_type: BTreePageType,
-    //first_freeblock_offset: u16,
+    pub first_freeblock_offset: u16,
     pub num_cells: u16,
-    //cell_content_area: u16,
-    //fragment_bytes: u8,
+    pub cell_content_area: u16,
+    pub fragment_bytes: u8,
     pub right_most_pointer: Option<u32>,
-    cell_pointers: Vec<u16>,
-    pub cells: Vec<BTreeCell>,
+    pub usable_size: usize,
+    pub text_encoding: TextEncoding,
+    header_offset: usize,
+    pub cell_pointers: Vec<u16>,
+    pub freeblocks: Vec<(u16, u16)>,
+    data: PageBytes,
 }
 
-impl<'a> BTreePage {
-    fn new(page: Vec<u8>) -> Result<Self> {
-        let mut b_tree_page = Self::read_header(&page)?;
-        b_tree_page.cells = b_tree_page.read_cells(&page)?;
+impl BTreePage {
+    pub fn new(
+        page_number: u32,
+        page: PageBytes,
+        header: &DatabaseHeader,
+    ) -> Result<Self, DecodeError> {
+        Self::with_offset_header(page_number, page, 0, header)
+    }
+
+    /// Parses a page whose b-tree header starts at `offset`, after the
+    /// database header on the first page.
+    pub fn with_offset_header(
+        page_number: u32,
+        mut page: PageBytes,
+        offset: usize,
+        header: &DatabaseHeader,
+    ) -> Result<Self, DecodeError> {
+        let usable_size = header.usable_size() as usize;
+
+        if page.len() < usable_size {
+            return Err(DecodeErrorKind::UnexpectedEnd.at(page_number, page.len()));
+        }
+        page.truncate(usable_size);
+
+        let mut b_tree_page = Self::read_header(page_number, &page, offset)?;
+        b_tree_page.usable_size = usable_size;
+        b_tree_page.text_encoding = header.text_encoding;
+        b_tree_page.freeblocks = b_tree_page.read_freeblocks(&page)?;
+        b_tree_page.check_cell_pointers()?;
+        b_tree_page.data = page;
 
         Ok(b_tree_page)
     }
 
-    fn with_offset_header(page: Vec<u8>, offset: usize) -> Result<Self> {
-        let mut b_tree_page = Self::read_header(&page[offset..])?;
-        b_tree_page.cells = b_tree_page.read_cells(&page)?;
+    pub fn cell_count(&self) -> usize {
+        self.cell_pointers.len()
+    }
 
-        Ok(b_tree_page)
+    /// Decodes the cell at `index`, borrowing its payload from the page.
+    /// Payloads that spill into overflow pages are cut short, see
+    /// `PageReader::read_overflow`.
+    pub fn cell(&self, index: usize) -> Option<Result<BTreeCell<'_>, DecodeError>> {
+        let cell_pointer = *self.cell_pointers.get(index)? as usize;
+
+        Some(
+            self.read_cell(&self.data[cell_pointer..], cell_pointer)
+                .map_err(|kind| kind.at(self.page_number, cell_pointer)),
+        )
     }
 
-    pub fn read_cells(&self, page: &Vec<u8>) -> Result<Vec<BTreeCell>> {
-        let mut cells = Vec::new();
-        for cell_pointer in &self.cell_pointers {
-            let page_slice = &page[*cell_pointer as usize..];
-            cells.push(match self.page_type {
-                BTreePageType::InteriorIndexPage => {
-                    let left_child_page = u32::from_be_bytes(page_slice[..4].try_into()?);
-                    let page_slice = &page_slice[4..];
-                    let (payload_size, page_slice) = Varint::from(page_slice);
-
-                    let payload_size_val = payload_size.value as usize;
-                    BTreeCell::InteriorIndexCell(BTreeInteriorIndexCell {
-                        left_child_page,
-                        //payload_size,
-                        payload: page_slice[..(payload_size_val as usize)].to_vec(),
-                        //first_overflow_page: 0,
-                    })
-                }
-                BTreePageType::InteriorTablePage => {
-                    let left_child_page = u32::from_be_bytes(page_slice[..4].try_into()?);
-                    let (row_id, _) = Varint::from(&page_slice[4..]);
-
-                    BTreeCell::InteriorTableCell(BTreeInteriorTableCell {
-                        left_child_page,
-                        row_id,
-                    })
-                }
-                BTreePageType::LeafTablePage => {
-                    let (payload_size, page_slice) = Varint::from(page_slice);
-                    let (row_id, page_slice) = Varint::from(page_slice);
-
-                    let payload_size_val = payload_size.value as usize;
-                    BTreeCell::LeafTableCell(BTreeLeafTableCell {
-                        //payload_size,
-                        row_id,
-                        payload: page_slice[..(payload_size_val as usize)].to_vec(),
-                        //first_overflow_page: 0,
-                    })
-                }
-                BTreePageType::LeafIndexPage => {
-                    let (payload_size, page_slice) = Varint::from(page_slice);
-
-                    let payload_size_val = payload_size.value as usize;
-                    BTreeCell::LeafIndexCell(BTreeLeafIndexCell {
-                        //payload_size,
-                        payload: page_slice[..(payload_size_val as usize)].to_vec(),
-                        //first_overflow_page: 0,
-                    })
+    pub fn cells(&self) -> impl Iterator<Item = Result<BTreeCell<'_>, DecodeError>> {
+        (0..self.cell_count()).filter_map(|index| self.cell(index))
+    }
+
+    /// Decodes the leaf table cell at `index` into its row id and the part
+    /// of its payload stored on the page, borrowed from it.
+    pub fn leaf_table_record(&self, index: usize) -> Result<(i64, &[u8]), DecodeError> {
+        match self.cell(index) {
+            Some(Ok(BTreeCell::LeafTableCell(BTreeLeafTableCell {
+                row_id,
+                payload: Cow::Borrowed(payload),
+                ..
+            }))) => Ok((row_id.value, payload)),
+            Some(Err(error)) => Err(error),
+            _ => Err(DecodeErrorKind::UnexpectedPageType("table b-tree")
+                .at(self.page_number, self.header_offset)),
+        }
+    }
+
+    /// Binary searches a table page for the first cell whose row id is at
+    /// least `row_id`, decoding only the cells it visits.
+    pub fn search_row_id(&self, row_id: i64) -> Result<usize, DecodeError> {
+        let (mut low, mut high) = (0, self.cell_count());
+
+        while low < high {
+            let middle = (low + high) / 2;
+
+            let cell_row_id = match self.cell(middle) {
+                Some(Ok(BTreeCell::InteriorTableCell(cell))) => cell.row_id.value,
+                Some(Ok(BTreeCell::LeafTableCell(cell))) => cell.row_id.value,
+                Some(Err(error)) => return Err(error),
+                _ => {
+                    return Err(DecodeErrorKind::UnexpectedPageType("table b-tree")
+                        .at(self.page_number, self.header_offset))
                 }
-            });
+            };
+
+            if cell_row_id < row_id {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+
+        Ok(low)
+    }
+
+    /// Number of payload bytes stored on the page itself, the rest spills
+    /// into the overflow chain.
+    pub fn local_payload_size(&self, payload_size: usize) -> usize {
+        let usable_size = self.usable_size;
+        let max_local = match self.page_type {
+            BTreePageType::LeafTablePage => usable_size - 35,
+            _ => (usable_size - 12) * 64 / 255 - 23,
+        };
+
+        if payload_size <= max_local {
+            return payload_size;
         }
 
-        Ok(cells)
+        let min_local = (usable_size - 12) * 32 / 255 - 23;
+        let local = min_local + (payload_size - min_local) % (usable_size - 4);
+
+        if local <= max_local {
+            local
+        } else {
+            min_local
+        }
     }
 
-    fn read_header(page: &[u8]) -> Result<Self> {
-        let page_type = match page[0] {
+    fn read_payload<'b>(
+        &self,
+        page_slice: &'b [u8],
+        payload_size: usize,
+    ) -> Result<(&'b [u8], Option<u32>), DecodeErrorKind> {
+        let local_size = self.local_payload_size(payload_size);
+
+        let (payload, rest) = page_slice
+            .split_at_checked(local_size)
+            .ok_or(DecodeErrorKind::UnexpectedEnd)?;
+
+        if local_size == payload_size {
+            return Ok((payload, None));
+        }
+
+        let (first_overflow_page, _) = read_u32(rest)?;
+
+        Ok((payload, Some(first_overflow_page)))
+    }
+
+    /// Offset of the first byte after the cell pointer array.
+    pub fn cell_pointer_end(&self) -> usize {
+        let header_len = match self.right_most_pointer {
+            Some(_) => 12,
+            None => 8,
+        };
+
+        self.header_offset + header_len + self.cell_pointers.len() * 2
+    }
+
+    /// Number of bytes a cell occupies on the page.
+    pub fn cell_size(&self, cell: &BTreeCell) -> usize {
+        let (header_size, payload_size, first_overflow_page) = match cell {
+            BTreeCell::InteriorIndexCell(cell) => (
+                4 + cell.payload_size.size as usize,
+                &cell.payload_size,
+                cell.first_overflow_page,
+            ),
+            BTreeCell::InteriorTableCell(cell) => return 4 + cell.row_id.size as usize,
+            BTreeCell::LeafIndexCell(cell) => (
+                cell.payload_size.size as usize,
+                &cell.payload_size,
+                cell.first_overflow_page,
+            ),
+            BTreeCell::LeafTableCell(cell) => (
+                (cell.payload_size.size + cell.row_id.size) as usize,
+                &cell.payload_size,
+                cell.first_overflow_page,
+            ),
+        };
+
+        let overflow_size = first_overflow_page.map_or(0, |_| 4);
+
+        header_size + self.local_payload_size(payload_size.value as usize) + overflow_size
+    }
+
+    /// Follows the freeblock list, yielding the offset and size of each block.
+    fn read_freeblocks(&self, page: &[u8]) -> Result<Vec<(u16, u16)>, DecodeError> {
+        let mut freeblocks = Vec::new();
+        let mut offset = self.first_freeblock_offset as usize;
+
+        while offset != 0 {
+            let error = DecodeErrorKind::InvalidFreeblock.at(self.page_number, offset);
+
+            if offset < self.cell_pointer_end() || offset + 4 > page.len() {
+                return Err(error);
+            }
+
+            let next = u16::from_be_bytes([page[offset], page[offset + 1]]) as usize;
+            let size = u16::from_be_bytes([page[offset + 2], page[offset + 3]]);
+
+            // Blocks must fit on the page and come in increasing order.
+            let end = offset + size as usize;
+            if end > page.len() || (next != 0 && next <= end) {
+                return Err(error);
+            }
+
+            freeblocks.push((offset as u16, size));
+            offset = next;
+        }
+
+        Ok(freeblocks)
+    }
+
+    /// Checks that every cell starts past the cell pointer array and on the
+    /// page, so cells can later be decoded without bounds surprises.
+    fn check_cell_pointers(&self) -> Result<(), DecodeError> {
+        for cell_pointer in &self.cell_pointers {
+            let cell_pointer = *cell_pointer as usize;
+
+            if cell_pointer < self.cell_pointer_end() || cell_pointer >= self.usable_size {
+                return Err(DecodeErrorKind::CellOffsetOutOfRange(cell_pointer)
+                    .at(self.page_number, self.cell_pointer_end()));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_cell<'b>(
+        &self,
+        page_slice: &'b [u8],
+        cell_pointer: usize,
+    ) -> Result<BTreeCell<'b>, DecodeErrorKind> {
+        let payload_offset = |payload: &[u8]| cell_pointer + page_slice.len() - payload.len();
+
+        Ok(match self.page_type {
+            BTreePageType::InteriorIndexPage => {
+                let (left_child_page, rest) = read_u32(page_slice)?;
+                let (payload_size, rest) = Varint::from(rest)?;
+
+                let (payload, first_overflow_page) =
+                    self.read_payload(rest, payload_size.value as usize)?;
+                BTreeCell::InteriorIndexCell(BTreeInteriorIndexCell {
+                    left_child_page,
+                    payload_size,
+                    payload: Cow::Borrowed(payload),
+                    first_overflow_page,
+                    text_encoding: self.text_encoding,
+                    page_number: self.page_number,
+                    payload_offset: payload_offset(rest),
+                })
+            }
+            BTreePageType::InteriorTablePage => {
+                let (left_child_page, rest) = read_u32(page_slice)?;
+                let (row_id, _) = Varint::from(rest)?;
+
+                BTreeCell::InteriorTableCell(BTreeInteriorTableCell {
+                    left_child_page,
+                    row_id,
+                })
+            }
+            BTreePageType::LeafTablePage => {
+                let (payload_size, rest) = Varint::from(page_slice)?;
+                let (row_id, rest) = Varint::from(rest)?;
+
+                let (payload, first_overflow_page) =
+                    self.read_payload(rest, payload_size.value as usize)?;
+                BTreeCell::LeafTableCell(BTreeLeafTableCell {
+                    payload_size,
+                    row_id,
+                    payload: Cow::Borrowed(payload),
+                    first_overflow_page,
+                    text_encoding: self.text_encoding,
+                    page_number: self.page_number,
+                    payload_offset: payload_offset(rest),
+                })
+            }
+            BTreePageType::LeafIndexPage => {
+                let (payload_size, rest) = Varint::from(page_slice)?;
+
+                let (payload, first_overflow_page) =
+                    self.read_payload(rest, payload_size.value as usize)?;
+                BTreeCell::LeafIndexCell(BTreeLeafIndexCell {
+                    payload_size,
+                    payload: Cow::Borrowed(payload),
+                    first_overflow_page,
+                    text_encoding: self.text_encoding,
+                    page_number: self.page_number,
+                    payload_offset: payload_offset(rest),
+                })
+            }
+        })
+    }
+
+    fn read_header(page_number: u32, page: &[u8], offset: usize) -> Result<Self, DecodeError> {
+        let header = page
+            .get(offset..)
+            .filter(|header| header.len() >= 8)
+            .ok_or_else(|| DecodeErrorKind::UnexpectedEnd.at(page_number, offset))?;
+
+        let page_type = match header[0] {
             0x02 => BTreePageType::InteriorIndexPage,
             0x05 => BTreePageType::InteriorTablePage,
             0x0a => BTreePageType::LeafIndexPage,
             0x0d => BTreePageType::LeafTablePage,
-            _ => bail!("Invalid page type {}", page[0]),
+            page_type => {
+                return Err(DecodeErrorKind::InvalidPageType(page_type).at(page_number, offset))
+            }
         };
 
         let mut header_len = 8;
-        let _first_freeblock_offset = u16::from_be_bytes([page[1], page[2]]);
-        let num_cells = u16::from_be_bytes([page[3], page[4]]);
-        let _cell_content_area = u16::from_be_bytes([page[5], page[6]]);
-        let _fragment_bytes = page[7];
+        let first_freeblock_offset = u16::from_be_bytes([header[1], header[2]]);
+        let num_cells = u16::from_be_bytes([header[3], header[4]]);
+        let cell_content_area = u16::from_be_bytes([header[5], header[6]]);
+        let fragment_bytes = header[7];
         let mut right_most_pointer = None;
         if page_type == BTreePageType::InteriorIndexPage
             || page_type == BTreePageType::InteriorTablePage
         {
-            right_most_pointer = Some(u32::from_be_bytes([page[8], page[9], page[10], page[11]]));
+            let (pointer, _) =
+                read_u32(&header[8..]).map_err(|kind| kind.at(page_number, offset + header_len))?;
+            right_most_pointer = Some(pointer);
             header_len += 4;
         }
 
-        let mut cell_pointers = Vec::new();
-
         let cell_pointer_size = num_cells as usize * 2 + header_len;
 
-        for chunk in page[header_len..cell_pointer_size].chunks(2) {
-            cell_pointers.push(u16::from_be_bytes([chunk[0], chunk[1]]));
-        }
+        let cell_pointers = header
+            .get(header_len..cell_pointer_size)
+            .ok_or_else(|| {
+                DecodeErrorKind::TooManyCells(num_cells).at(page_number, offset + header_len)
+            })?
+            .chunks(2)
+            .map(|chunk| u16::from_be_bytes([chunk[0], chunk[1]]))
+            .collect();
 
         Ok(BTreePage {
+            page_number,
             page_type,
-            //first_freeblock_offset,
+            first_freeblock_offset,
             num_cells,
-            //cell_content_area,
-            //fragment_bytes,
+            cell_content_area,
+            fragment_bytes,
             right_most_pointer,
+            usable_size: page.len(),
+            text_encoding: TextEncoding::Utf8,
+            header_offset: offset,
             cell_pointers,
-            cells: Vec::new(),
+            freeblocks: Vec::new(),
+            data: PageBytes::Owned(Vec::new()),
         })
     }
-
-    pub fn iter(self) -> Result<CellIterator> {
-        CellIterator::new(self)
-    }
 }
 
 #[derive(PartialEq, Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum BTreePageType {
     InteriorIndexPage = 0x02,
     InteriorTablePage = 0x05,
     LeafIndexPage = 0x0a,
     LeafTablePage = 0x0d,
 }
+
+/// Reads a big-endian u32 from the start of `buf`.
+fn read_u32(buf: &[u8]) -> Result<(u32, &[u8]), DecodeErrorKind> {
+    let (bytes, rest) = buf
+        .split_first_chunk::<4>()
+        .ok_or(DecodeErrorKind::UnexpectedEnd)?;
+
+    Ok((u32::from_be_bytes(*bytes), rest))
+}
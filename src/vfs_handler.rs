@@ -0,0 +1,154 @@
+use super::mmap_handler::Mmap;
+
+use anyhow::{anyhow, Result};
+use std::fmt::Debug;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::{Deref, Range};
+use std::rc::Rc;
+
+/// How pages of the main database file are accessed.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum PageAccess {
+    #[default]
+    File,
+    Mmap,
+}
+
+/// Read-only storage holding a database image, the byte source the page
+/// reader runs over.
+pub trait PageSource: Debug {
+    /// Size of the image in bytes.
+    fn len(&self) -> Result<u64>;
+
+    /// Reads `len` bytes at `offset`, sharing them with the source instead
+    /// of copying when it keeps the whole image in memory.
+    fn read(&mut self, offset: u64, len: usize) -> Result<PageBytes>;
+}
+
+/// A whole database image kept in memory, which pages can share.
+pub trait Image: Deref<Target = [u8]> + Debug {}
+
+impl<T: Deref<Target = [u8]> + Debug> Image for T {}
+
+/// Bytes read from a page source, either a range of an in-memory image,
+/// kept alive by the pages holding it, or a buffer of their own for sources
+/// that can't lend them.
+#[derive(Debug, Clone)]
+pub enum PageBytes {
+    Owned(Vec<u8>),
+    Shared(Rc<dyn Image>, Range<usize>),
+}
+
+impl PageBytes {
+    /// Drops the bytes past `len`.
+    pub fn truncate(&mut self, len: usize) {
+        match self {
+            PageBytes::Owned(bytes) => bytes.truncate(len),
+            PageBytes::Shared(_, range) => range.end = range.end.min(range.start + len),
+        }
+    }
+}
+
+impl Deref for PageBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            PageBytes::Owned(bytes) => bytes,
+            PageBytes::Shared(image, range) => &image[range.clone()],
+        }
+    }
+}
+
+impl From<Vec<u8>> for PageBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        PageBytes::Owned(bytes)
+    }
+}
+
+/// Opens a database file, mapping it into memory when asked to and falling
+/// back to plain reads where mapping isn't possible.
+pub fn open(file_name: &str, access: PageAccess) -> Result<Box<dyn PageSource>> {
+    let file = File::open(file_name)?;
+
+    match access {
+        PageAccess::Mmap => match Mmap::map(&file) {
+            Ok(mmap) => Ok(Box::new(MemorySource::from(mmap))),
+            Err(_) => Ok(Box::new(file)),
+        },
+        PageAccess::File => Ok(Box::new(file)),
+    }
+}
+
+impl PageSource for File {
+    fn len(&self) -> Result<u64> {
+        Ok(self.metadata()?.len())
+    }
+
+    fn read(&mut self, offset: u64, len: usize) -> Result<PageBytes> {
+        let mut page = vec![0; len];
+        self.seek(SeekFrom::Start(offset))?;
+        self.read_exact(&mut page)?;
+
+        Ok(PageBytes::Owned(page))
+    }
+}
+
+/// A database image held in memory, either owned, like a database read
+/// from stdin or downloaded, static, like one embedded with
+/// `include_bytes!`, or a file mapped into memory.
+#[derive(Debug)]
+pub struct MemorySource {
+    bytes: Rc<dyn Image>,
+}
+
+impl MemorySource {
+    /// Reads a whole database image from stdin.
+    pub fn from_stdin() -> Result<Self> {
+        let mut bytes = Vec::new();
+        std::io::stdin().lock().read_to_end(&mut bytes)?;
+
+        Ok(MemorySource::from(bytes))
+    }
+}
+
+impl From<Vec<u8>> for MemorySource {
+    fn from(bytes: Vec<u8>) -> Self {
+        MemorySource {
+            bytes: Rc::new(bytes),
+        }
+    }
+}
+
+impl From<&'static [u8]> for MemorySource {
+    fn from(bytes: &'static [u8]) -> Self {
+        MemorySource {
+            bytes: Rc::new(bytes),
+        }
+    }
+}
+
+impl From<Mmap> for MemorySource {
+    fn from(mmap: Mmap) -> Self {
+        MemorySource {
+            bytes: Rc::new(mmap),
+        }
+    }
+}
+
+impl PageSource for MemorySource {
+    fn len(&self) -> Result<u64> {
+        Ok(self.bytes.len() as u64)
+    }
+
+    fn read(&mut self, offset: u64, len: usize) -> Result<PageBytes> {
+        let start = usize::try_from(offset)?;
+
+        start
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .map(|end| PageBytes::Shared(self.bytes.clone(), start..end))
+            .ok_or_else(|| anyhow!("Read at offset {} is past the end of the database", offset))
+    }
+}